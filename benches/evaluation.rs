@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_json::{json, Value};
+use statsig_rdp::{
+    __bench::{ConfigData, Evaluator},
+    models::StatsigUser,
+};
+
+/// Number of entries in the `any`/`none` target lists, big lists are where the
+/// per-evaluation work used to add up.
+const LIST_SIZE: usize = 500;
+
+fn rule(id: &str, pass_percentage: u64, conditions: Value) -> Value {
+    json!({
+        "name": id,
+        "groupName": id,
+        "id": id,
+        "salt": format!("{}_salt", id),
+        "passPercentage": pass_percentage,
+        "idType": "userID",
+        "returnValue": {"rule": id},
+        "conditions": conditions,
+    })
+}
+
+fn condition(r#type: &str, operator: &str, field: &str, target: Value) -> Value {
+    json!({
+        "type": r#type,
+        "operator": operator,
+        "field": field,
+        "targetValue": target,
+        "idType": "userID",
+    })
+}
+
+fn spec(name: &str, r#type: &str, rules: Vec<Value>) -> Value {
+    json!({
+        "name": name,
+        "type": r#type,
+        "salt": format!("{}_salt", name),
+        "enabled": true,
+        "defaultValue": if r#type == "feature_gate" { json!(false) } else { json!({}) },
+        "idType": "userID",
+        "rules": rules,
+    })
+}
+
+fn config_specs() -> Value {
+    let emails: Vec<String> = (0..LIST_SIZE)
+        .map(|i| format!("User{}@Example.com", i))
+        .collect();
+    let countries: Vec<String> = (0..LIST_SIZE).map(|i| format!("C{}", i)).collect();
+    json!({
+        "feature_gates": [
+            spec("email_list", "feature_gate", vec![rule(
+                "email_list_rule",
+                100,
+                json!([condition("user_field", "any", "email", json!(emails))]),
+            )]),
+            spec("country_blocklist", "feature_gate", vec![rule(
+                "country_rule",
+                100,
+                json!([condition("user_field", "none", "country", json!(countries))]),
+            )]),
+            spec("app_version", "feature_gate", vec![rule(
+                "app_version_rule",
+                100,
+                json!([
+                    condition("user_field", "version_gte", "appVersion", json!("2.10.0-beta.1")),
+                    condition("user_field", "version_lt", "appVersion", json!("4.0.0")),
+                ]),
+            )]),
+            spec("signed_up_early", "feature_gate", vec![rule(
+                "signed_up_rule",
+                100,
+                json!([
+                    condition("user_field", "after", "signupDate", json!("2020-01-01T00:00:00Z")),
+                    condition("user_field", "before", "signupDate", json!("2030-01-01")),
+                ]),
+            )]),
+            spec("domains", "feature_gate", vec![rule(
+                "domains_rule",
+                100,
+                json!([condition(
+                    "user_field",
+                    "str_ends_with_any",
+                    "email",
+                    json!(["@Example.com", "@Example.org", "@Example.net"]),
+                )]),
+            )]),
+            spec("all_gates", "feature_gate", vec![rule(
+                "all_gates_rule",
+                50,
+                json!([
+                    condition("pass_gate", "any", "", json!("email_list")),
+                    condition("pass_gate", "any", "", json!("country_blocklist")),
+                    condition("pass_gate", "any", "", json!("app_version")),
+                    condition("pass_gate", "any", "", json!("signed_up_early")),
+                    condition("pass_gate", "any", "", json!("domains")),
+                ]),
+            )]),
+        ],
+        "dynamic_configs": [
+            spec("targeting", "dynamic_config", vec![
+                rule(
+                    "level_rule",
+                    100,
+                    json!([condition("user_field", "gte", "level", json!("100"))]),
+                ),
+                rule(
+                    "gated_rule",
+                    100,
+                    json!([condition("pass_gate", "any", "", json!("all_gates"))]),
+                ),
+            ]),
+        ],
+        "layer_configs": [],
+        "has_updates": true,
+        "time": 0,
+    })
+}
+
+fn create_evaluator() -> Evaluator {
+    let evaluator = Evaluator::new();
+    evaluator.refresh_configs(
+        serde_json::from_value::<ConfigData>(config_specs()).expect("specs should be valid"),
+    );
+    evaluator
+}
+
+fn user() -> StatsigUser {
+    StatsigUser {
+        email: Some(format!("user{}@example.com", LIST_SIZE - 1)),
+        country: Some("BR".to_string()),
+        app_version: Some("3.2.1".to_string()),
+        custom: Some(HashMap::from([(
+            "signupDate".to_string(),
+            "2024-03-01T10:00:00Z".to_string(),
+        )])),
+        custom_values: Some(HashMap::from([("level".to_string(), json!(7))])),
+        ..StatsigUser::new("user_id".to_string(), "production".to_string())
+    }
+}
+
+/// Drives the evaluator directly, so neither exposure logging nor the client are measured.
+fn bench_evaluation(c: &mut Criterion) {
+    let evaluator = create_evaluator();
+    let user = user();
+
+    let mut group = c.benchmark_group("check_gate");
+    for gate in [
+        "email_list",
+        "country_blocklist",
+        "app_version",
+        "signed_up_early",
+        "domains",
+        "all_gates",
+    ] {
+        group.bench_with_input(BenchmarkId::from_parameter(gate), gate, |b, gate| {
+            b.iter(|| evaluator.check_gate_internal(&user, gate))
+        });
+    }
+    group.finish();
+
+    c.bench_function("get_config", |b| {
+        b.iter(|| evaluator.get_dynamic_config_internal(&user, "targeting"))
+    });
+
+    c.bench_function("get_all_evaluations", |b| {
+        b.iter(|| evaluator.get_all_evaluations_internal(&user))
+    });
+}
+
+criterion_group!(benches, bench_evaluation);
+criterion_main!(benches);
@@ -1,35 +1,76 @@
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, PoisonError},
     time::SystemTime,
 };
 
-use anyhow::{anyhow, bail, Result};
+use crossbeam::sync::{ShardedLock, ShardedLockReadGuard, ShardedLockWriteGuard};
 use serde::de::DeserializeOwned;
-use tokio::{time, time::Duration};
+use tokio::{runtime::Handle, time, time::Duration};
 use tracing::{event, Level};
 
 use crate::{
-    evaluator::{models::EvalResult, Evaluator},
+    error::{from_value, Result, StatsigError},
+    eval_cache::{EvalCache, SpecKind},
+    evaluator::{geoip::IpCountryLookup, models::EvalResult, Evaluator},
     http::StatsigHttpClient,
     models::{
-        ExperimentExposure, ExperimentExposurePost, SecondaryExposure, StatsigConfig, StatsigEvent,
-        StatsigExperiment, StatsigMetadata, StatsigOptions, StatsigPost, StatsigUser,
+        AllEvaluations, ClientInitializeResponse, ClientInitializeResponseOptions,
+        EvaluationDetails, EvaluationReason, ExperimentExposure, ExperimentExposurePost,
+        Explanation, ExplanationOutcome, ExposureEvent, FeatureGate, Layer, LocalResult,
+        OverrideTarget, ParameterExposureLogger, SecondaryExposure, SpecEvaluation, StatsigConfig,
+        StatsigEvent, StatsigExperiment, StatsigMetadata, StatsigOptions, StatsigPost, StatsigUser,
     },
+    overrides::Overrides,
 };
 
 const GATE_EXPOSURE_EVENT: &str = "statsig::gate_exposure";
 const CONFIG_EXPOSURE_EVENT: &str = "statsig::config_exposure";
+const LAYER_EXPOSURE_EVENT: &str = "statsig::layer_exposure";
 const MAX_LOG_EVENTS: usize = 950;
 const RUST_SDK_TYPE: &str = "rust-server";
 const RUST_SDK_VERSION: &str = "0.9.0";
+/// Rule id reported for values coming from a local override.
+const OVERRIDE_RULE_ID: &str = "override";
 
 /// Statsig client that has a local cache and syncs with the API periodically.
 pub struct Client {
     disable_cache: bool,
     http_client: StatsigHttpClient,
     evaluator: Evaluator,
-    event_logs: Mutex<Vec<StatsigEvent>>,
+    /// Results of local evaluations, when enabled with `evaluation_cache_size`.
+    eval_cache: Option<EvalCache>,
+    logger: Arc<EventLogger>,
+    overrides: ShardedLock<Overrides>,
+}
+
+/// Events waiting to be sent. It's shared with the layers handed out, which log parameter
+/// exposures when they're read.
+struct EventLogger {
+    http_client: StatsigHttpClient,
+    events: Mutex<Vec<ExposureEvent>>,
+    /// Runtime the client was created in, exposures are sent from it so they can be logged
+    /// from synchronous code.
+    runtime: Handle,
+}
+
+impl EventLogger {
+    fn take(&self) -> Vec<ExposureEvent> {
+        let mut events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        std::mem::take(&mut *events)
+    }
+
+    /// Queues the event, sending the queue in the background once it's full.
+    fn push(&self, event: ExposureEvent) {
+        let mut events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        events.push(event);
+        if events.len() >= MAX_LOG_EVENTS {
+            let events = std::mem::take(&mut *events);
+            let http_client = self.http_client.clone();
+            self.runtime
+                .spawn(async move { post_logs(&http_client, events).await });
+        }
+    }
 }
 
 impl Client {
@@ -41,7 +82,11 @@ impl Client {
             options.events_url,
         );
 
-        let evaluator = Evaluator::new();
+        let mut evaluator =
+            Evaluator::new().with_strict_versions(options.strict_version_comparison);
+        if let Some(path) = &options.geoip_database_path {
+            evaluator = evaluator.with_ip_country_lookup(IpCountryLookup::from_csv_file(path)?);
+        }
         if !options.disable_cache {
             let initial_data = http_client.fetch_state_from_source().await?;
             evaluator.refresh_configs(initial_data);
@@ -50,12 +95,25 @@ impl Client {
         let s = Arc::new(Self {
             disable_cache: options.disable_cache,
             evaluator,
+            eval_cache: options.evaluation_cache_size.and_then(EvalCache::new),
+            logger: Arc::new(EventLogger {
+                http_client: http_client.clone(),
+                events: Mutex::new(vec![]),
+                runtime: Handle::current(),
+            }),
             http_client,
-            event_logs: Mutex::new(vec![]),
+            overrides: ShardedLock::new(Overrides::default()),
         });
 
         if !options.disable_cache {
+            if let Err(e) = s.http_client.sync_id_lists(s.evaluator.id_lists()).await {
+                event!(Level::ERROR, "Failed to sync id lists: {}", e);
+            }
             tokio::spawn(s.clone().poll_for_changes(options.config_sync_interval));
+            tokio::spawn(
+                s.clone()
+                    .poll_for_id_list_changes(options.config_sync_interval),
+            );
             tokio::spawn(s.clone().background_logs_flush());
         }
 
@@ -63,22 +121,65 @@ impl Client {
     }
 
     pub async fn check_gate(self: Arc<Self>, gate: String, user: StatsigUser) -> Result<bool> {
-        if user.user_id.is_empty() {
-            bail!("statsig: missing user id");
+        Ok(self.get_feature_gate(gate, user).await?.value)
+    }
+
+    /// Checks the gate, together with the details of where the value came from.
+    pub async fn get_feature_gate(
+        self: Arc<Self>,
+        gate: String,
+        user: StatsigUser,
+    ) -> Result<FeatureGate> {
+        match self.get_feature_gate_local(&gate, &user)? {
+            LocalResult::Value(val) => Ok(val),
+            LocalResult::NeedsNetwork => self.get_feature_gate_from_network(gate, user).await,
+        }
+    }
+
+    /// Checks the gate without calling the API, see `get_feature_gate_local`.
+    pub fn check_gate_local(&self, gate: &str, user: &StatsigUser) -> Result<LocalResult<bool>> {
+        Ok(self
+            .get_feature_gate_local(gate, user)?
+            .map(|val| val.value))
+    }
+
+    /// Checks the gate synchronously, without calling the API. When only the API can evaluate
+    /// it, `LocalResult::NeedsNetwork` is returned and nothing is logged.
+    pub fn get_feature_gate_local(
+        &self,
+        gate: &str,
+        user: &StatsigUser,
+    ) -> Result<LocalResult<FeatureGate>> {
+        validate_user(user)?;
+
+        if let Some(pass) = self.read_overrides().gate(gate, user) {
+            return Ok(LocalResult::Value(FeatureGate {
+                name: gate.to_string(),
+                value: pass,
+                rule_id: OVERRIDE_RULE_ID.to_string(),
+                group_name: None,
+                evaluation_details: self.evaluation_details(EvaluationReason::LocalOverride),
+            }));
         }
 
         if self.disable_cache {
-            return self.http_client.check_gate(gate, user).await;
+            return Ok(LocalResult::NeedsNetwork);
         }
 
-        let res = self.evaluator.check_gate_internal(&user, &gate);
+        let res = self.evaluate(SpecKind::Gate, gate, user, Evaluator::check_gate_internal);
         if res.fetch_from_server {
-            self.http_client.check_gate(gate, user).await
-        } else {
-            let pass = res.pass;
-            self.log_gate_exposure(gate, user, res);
-            Ok(pass)
+            return Ok(LocalResult::NeedsNetwork);
         }
+
+        let val = FeatureGate {
+            name: gate.to_string(),
+            value: res.pass,
+            rule_id: res.rule_id.clone(),
+            group_name: res.group_name.clone(),
+            evaluation_details: self.result_details(&res),
+        };
+        self.log_gate_exposure(gate, user, res);
+        Ok(LocalResult::Value(val))
     }
 
     pub async fn get_dynamic_config<T: DeserializeOwned>(
@@ -86,23 +187,47 @@ impl Client {
         config: String,
         user: StatsigUser,
     ) -> Result<T> {
-        if user.user_id.is_empty() {
-            bail!("statsig: missing user id");
+        match self.get_dynamic_config_local(&config, &user)? {
+            LocalResult::Value(val) => Ok(val),
+            LocalResult::NeedsNetwork => self.http_client.get_dynamic_config(config, user).await,
+        }
+    }
+
+    /// Gets the config value synchronously, without calling the API. When only the API can
+    /// evaluate it, `LocalResult::NeedsNetwork` is returned and nothing is logged.
+    pub fn get_dynamic_config_local<T: DeserializeOwned>(
+        &self,
+        config: &str,
+        user: &StatsigUser,
+    ) -> Result<LocalResult<T>> {
+        validate_user(user)?;
+
+        if let Some(val) = self.config_override(config, user) {
+            return Ok(LocalResult::Value(from_value(val)?));
         }
 
         if self.disable_cache {
-            return self.http_client.get_dynamic_config(config, user).await;
+            return Ok(LocalResult::NeedsNetwork);
         }
 
-        let mut res = self.evaluator.get_dynamic_config_internal(&user, &config);
+        let mut res = self.evaluate(
+            SpecKind::DynamicConfig,
+            config,
+            user,
+            Evaluator::get_dynamic_config_internal,
+        );
         if res.fetch_from_server {
-            self.http_client.get_dynamic_config(config, user).await
-        } else {
-            let val = res.config_value.take();
-            self.log_config_exposure(config, user, res);
-            let val = val.ok_or_else(|| anyhow!("empty config"))?;
-            Ok(serde_json::from_value(val)?)
+            return Ok(LocalResult::NeedsNetwork);
         }
+
+        let val = res.config_value.take();
+        let reason = res.reason;
+        self.log_config_exposure(config, user, res);
+        let val = val.ok_or_else(|| match reason {
+            EvaluationReason::Uninitialized => StatsigError::Uninitialized,
+            _ => StatsigError::UnknownSpec(config.to_string()),
+        })?;
+        Ok(LocalResult::Value(from_value(val)?))
     }
 
     /// Returns the value, together with the metadata about the group that matched the check
@@ -111,36 +236,122 @@ impl Client {
         config: String,
         user: StatsigUser,
     ) -> Result<StatsigConfig<T>> {
-        if user.user_id.is_empty() {
-            bail!("statsig: missing user id");
+        match self.get_config_local(&config, &user)? {
+            LocalResult::Value(val) => Ok(val),
+            LocalResult::NeedsNetwork => self.get_config_from_network(config, user).await,
+        }
+    }
+
+    /// Gets the config with the metadata of the matched group synchronously, without calling
+    /// the API. When only the API can evaluate it, `LocalResult::NeedsNetwork` is returned and
+    /// nothing is logged.
+    pub fn get_config_local<T: DeserializeOwned>(
+        &self,
+        config: &str,
+        user: &StatsigUser,
+    ) -> Result<LocalResult<StatsigConfig<T>>> {
+        validate_user(user)?;
+
+        if let Some(val) = self.config_override(config, user) {
+            return Ok(LocalResult::Value(StatsigConfig {
+                value: from_value(val)?,
+                name: config.to_string(),
+                group_name: None,
+                rule_id: OVERRIDE_RULE_ID.to_string(),
+                group: OVERRIDE_RULE_ID.to_string(),
+                evaluation_details: Some(self.evaluation_details(EvaluationReason::LocalOverride)),
+            }));
         }
 
         if self.disable_cache {
-            return self.http_client.get_config(config, user).await;
+            return Ok(LocalResult::NeedsNetwork);
         }
 
-        let res = self.evaluator.get_dynamic_config_internal(&user, &config);
+        let res = self.evaluate(
+            SpecKind::DynamicConfig,
+            config,
+            user,
+            Evaluator::get_dynamic_config_internal,
+        );
         if res.fetch_from_server {
-            self.http_client.get_config(config, user).await
-        } else {
-            let value: Option<T> = serde_json::from_value(
-                res.config_value.clone().unwrap_or(serde_json::Value::Null),
-            )?;
-
-            let val = StatsigConfig {
-                value,
-                name: config.clone(),
-                group_name: res.group_name.clone(),
-                rule_id: res.rule_id.clone(),
-                group: res.group.clone(),
-            };
+            return Ok(LocalResult::NeedsNetwork);
+        }
 
-            self.log_config_exposure(config, user, res);
+        let value: Option<T> =
+            from_value(res.config_value.clone().unwrap_or(serde_json::Value::Null))?;
+
+        let val = StatsigConfig {
+            value,
+            name: config.to_string(),
+            group_name: res.group_name.clone(),
+            rule_id: res.rule_id.clone(),
+            group: res.group.clone(),
+            evaluation_details: Some(self.result_details(&res)),
+        };
 
-            Ok(val)
+        self.log_config_exposure(config, user, res);
+
+        Ok(LocalResult::Value(val))
+    }
+
+    /// Evaluates a layer locally, falling back to the API when it can't be evaluated.
+    pub async fn get_layer(self: Arc<Self>, layer: String, user: StatsigUser) -> Result<Layer> {
+        match self.get_layer_local(&layer, &user)? {
+            LocalResult::Value(val) => Ok(val),
+            LocalResult::NeedsNetwork => self.http_client.get_layer(layer, user).await,
         }
     }
 
+    /// Evaluates a layer synchronously, without calling the API. When only the API can
+    /// evaluate it, `LocalResult::NeedsNetwork` is returned and nothing is logged.
+    pub fn get_layer_local(&self, layer: &str, user: &StatsigUser) -> Result<LocalResult<Layer>> {
+        validate_user(user)?;
+
+        if self.disable_cache {
+            return Ok(LocalResult::NeedsNetwork);
+        }
+
+        let mut res = self.evaluate(SpecKind::Layer, layer, user, Evaluator::get_layer_internal);
+        if res.fetch_from_server {
+            return Ok(LocalResult::NeedsNetwork);
+        }
+
+        let value = match res.config_value.take() {
+            Some(serde_json::Value::Object(map)) => map.into_iter().collect(),
+            _ => HashMap::new(),
+        };
+        let secondary_exposures = res
+            .secondary_exposures
+            .iter()
+            .filter_map(SecondaryExposure::from_hashmap)
+            .collect();
+        let undelegated_secondary_exposures = res
+            .undelegated_secondary_exposures
+            .as_ref()
+            .unwrap_or(&res.secondary_exposures)
+            .iter()
+            .filter_map(SecondaryExposure::from_hashmap)
+            .collect();
+
+        let val = Layer {
+            value,
+            name: layer.to_string(),
+            rule_id: res.rule_id.clone(),
+            group_name: res.group_name.clone(),
+            allocated_experiment_name: res.config_delegate.clone(),
+            secondary_exposures,
+            undelegated_secondary_exposures,
+            exposure_logger: Some(self.layer_exposure_logger(layer, user, res)),
+        };
+
+        Ok(LocalResult::Value(val))
+    }
+
+    /// Sends the queued events now, instead of waiting for the periodic flush.
+    pub async fn flush(&self) {
+        post_logs(&self.http_client, self.logger.take()).await;
+    }
+
     pub async fn log_event(&self, statsig_post: &StatsigPost) -> Result<()> {
         self.http_client.log_event(statsig_post).await
     }
@@ -150,23 +361,10 @@ impl Client {
         experiment_name: String,
         user: StatsigUser,
     ) -> Result<StatsigExperiment<T>> {
-        if user.user_id.is_empty() {
-            bail!("statsig: missing user id");
-        }
+        validate_user(&user)?;
 
-        let config: StatsigConfig<T> = self
-            .http_client
-            .get_config(experiment_name.clone(), user)
-            .await?;
-
-        Ok(StatsigExperiment {
-            value: config.value,
-            name: config.name,
-            group_name: config.group_name,
-            rule_id: config.rule_id,
-            group: config.group,
-            secondary_exposures: vec![],
-        })
+        self.get_experiment_from_network(experiment_name, user)
+            .await
     }
 
     /// Gets an experiment and logs the exposure properly for holdout tracking.
@@ -181,44 +379,51 @@ impl Client {
         experiment_name: String,
         user: StatsigUser,
     ) -> Result<StatsigExperiment<T>> {
-        if user.user_id.is_empty() {
-            bail!("statsig: missing user id");
+        match self.get_experiment_local(&experiment_name, &user)? {
+            LocalResult::Value(val) => Ok(val),
+            LocalResult::NeedsNetwork => {
+                self.get_experiment_from_network(experiment_name, user)
+                    .await
+            }
         }
+    }
 
-        if self.disable_cache {
-            let config: StatsigConfig<T> = self
-                .http_client
-                .get_config(experiment_name.clone(), user)
-                .await?;
-
-            return Ok(StatsigExperiment {
-                value: config.value,
-                name: config.name,
-                group_name: config.group_name,
-                rule_id: config.rule_id,
-                group: config.group,
+    /// Gets an experiment synchronously, without calling the API. When only the API can
+    /// evaluate it, `LocalResult::NeedsNetwork` is returned and nothing is logged.
+    ///
+    /// The exposure is sent to `/log_custom_exposure` in the background, like in
+    /// `get_experiment`.
+    pub fn get_experiment_local<T: DeserializeOwned>(
+        &self,
+        experiment_name: &str,
+        user: &StatsigUser,
+    ) -> Result<LocalResult<StatsigExperiment<T>>> {
+        validate_user(user)?;
+
+        if let Some(val) = self.config_override(experiment_name, user) {
+            return Ok(LocalResult::Value(StatsigExperiment {
+                value: from_value(val)?,
+                name: experiment_name.to_string(),
+                group_name: None,
+                rule_id: OVERRIDE_RULE_ID.to_string(),
+                group: OVERRIDE_RULE_ID.to_string(),
                 secondary_exposures: vec![],
-            });
+                evaluation_details: Some(self.evaluation_details(EvaluationReason::LocalOverride)),
+            }));
         }
 
-        let res = self
-            .evaluator
-            .get_dynamic_config_internal(&user, &experiment_name);
+        if self.disable_cache {
+            return Ok(LocalResult::NeedsNetwork);
+        }
 
+        let res = self.evaluate(
+            SpecKind::DynamicConfig,
+            experiment_name,
+            user,
+            Evaluator::get_dynamic_config_internal,
+        );
         if res.fetch_from_server {
-            let config: StatsigConfig<T> = self
-                .http_client
-                .get_config(experiment_name.clone(), user)
-                .await?;
-
-            return Ok(StatsigExperiment {
-                value: config.value,
-                name: config.name,
-                group_name: config.group_name,
-                rule_id: config.rule_id,
-                group: config.group,
-                secondary_exposures: vec![],
-            });
+            return Ok(LocalResult::NeedsNetwork);
         }
 
         let secondary_exposures: Vec<SecondaryExposure> = res
@@ -228,35 +433,264 @@ impl Client {
             .collect();
 
         let value: Option<T> =
-            serde_json::from_value(res.config_value.clone().unwrap_or(serde_json::Value::Null))?;
-
-        self.clone()
-            .log_experiment_exposure_async(
-                experiment_name.clone(),
-                user,
-                res.group.clone(),
-                res.rule_id.clone(),
-                secondary_exposures.clone(),
-            )
-            .await;
+            from_value(res.config_value.clone().unwrap_or(serde_json::Value::Null))?;
+
+        self.log_experiment_exposure(
+            experiment_name.to_string(),
+            user.clone(),
+            res.group.clone(),
+            res.rule_id.clone(),
+            secondary_exposures.clone(),
+        );
 
-        Ok(StatsigExperiment {
+        let evaluation_details = Some(self.result_details(&res));
+        Ok(LocalResult::Value(StatsigExperiment {
             value,
-            name: experiment_name,
+            name: experiment_name.to_string(),
             group_name: res.group_name,
             rule_id: res.rule_id,
             group: res.group,
             secondary_exposures,
+            evaluation_details,
+        }))
+    }
+
+    /// Evaluates every gate, config and layer for the user, without logging exposures.
+    ///
+    /// All values come from the same version of the specs. Specs with conditions that can
+    /// only be evaluated by the API are left out.
+    pub fn get_all_evaluations(&self, user: &StatsigUser) -> Result<AllEvaluations> {
+        validate_user(user)?;
+
+        if self.disable_cache {
+            return Err(StatsigError::Uninitialized);
+        }
+
+        fn to_evaluations(
+            results: HashMap<String, EvalResult>,
+            value: fn(EvalResult) -> serde_json::Value,
+        ) -> HashMap<String, SpecEvaluation> {
+            results
+                .into_iter()
+                .filter(|(_, res)| !res.fetch_from_server)
+                .map(|(name, res)| {
+                    let secondary_exposures = res
+                        .secondary_exposures
+                        .iter()
+                        .filter_map(SecondaryExposure::from_hashmap)
+                        .collect();
+                    let evaluation = SpecEvaluation {
+                        rule_id: res.rule_id.clone(),
+                        group_name: res.group_name.clone(),
+                        secondary_exposures,
+                        value: value(res),
+                    };
+                    (name, evaluation)
+                })
+                .collect()
+        }
+
+        let results = self.evaluator.get_all_evaluations_internal(user);
+        let config_value = |res: EvalResult| res.config_value.unwrap_or(serde_json::Value::Null);
+        Ok(AllEvaluations {
+            feature_gates: to_evaluations(results.gates, |res| serde_json::Value::Bool(res.pass)),
+            dynamic_configs: to_evaluations(results.dynamic_configs, config_value),
+            layer_configs: to_evaluations(results.layer_configs, config_value),
         })
     }
+
+    /// Builds the response of the `/initialize` endpoint for the user, so a client SDK can be
+    /// bootstrapped without calling the API. No exposures are logged.
+    pub fn get_client_initialize_response(
+        &self,
+        user: &StatsigUser,
+        options: ClientInitializeResponseOptions,
+    ) -> Result<ClientInitializeResponse> {
+        validate_user(user)?;
+
+        if self.disable_cache {
+            return Err(StatsigError::Uninitialized);
+        }
+
+        Ok(self
+            .evaluator
+            .get_client_initialize_response_internal(user, &options))
+    }
+
+    /// Explains how the gate is evaluated for the user, without logging exposures.
+    pub fn explain_gate(&self, gate: &str, user: &StatsigUser) -> Result<Explanation> {
+        validate_user(user)?;
+
+        if let Some(pass) = self.read_overrides().gate(gate, user) {
+            return Ok(Explanation {
+                pass,
+                rule_id: OVERRIDE_RULE_ID.to_string(),
+                ..Explanation::new(gate, ExplanationOutcome::LocalOverride)
+            });
+        }
+
+        if self.disable_cache {
+            return Err(StatsigError::Uninitialized);
+        }
+
+        Ok(self.evaluator.explain_gate_internal(user, gate))
+    }
+
+    /// Explains how the config or experiment is evaluated for the user, without logging
+    /// exposures.
+    pub fn explain_config(&self, config: &str, user: &StatsigUser) -> Result<Explanation> {
+        validate_user(user)?;
+
+        if let Some(value) = self.config_override(config, user) {
+            return Ok(Explanation {
+                value: Some(value),
+                rule_id: OVERRIDE_RULE_ID.to_string(),
+                ..Explanation::new(config, ExplanationOutcome::LocalOverride)
+            });
+        }
+
+        if self.disable_cache {
+            return Err(StatsigError::Uninitialized);
+        }
+
+        Ok(self.evaluator.explain_dynamic_config_internal(user, config))
+    }
+
+    /// Makes `check_gate` return `value` for the target users, without logging exposures.
+    pub fn override_gate(&self, gate: String, value: bool, target: OverrideTarget) {
+        self.write_overrides().set_gate(gate, value, target);
+    }
+
+    /// Makes `get_config` and `get_dynamic_config` return `value` for the target users,
+    /// without logging exposures.
+    pub fn override_config(
+        &self,
+        config: String,
+        value: serde_json::Value,
+        target: OverrideTarget,
+    ) {
+        self.write_overrides().set_config(config, value, target);
+    }
+
+    /// Makes `get_experiment` return `value` for the target users, without logging exposures.
+    ///
+    /// Experiments are dynamic configs, so this also applies to `get_config`.
+    pub fn override_experiment(
+        &self,
+        experiment: String,
+        value: serde_json::Value,
+        target: OverrideTarget,
+    ) {
+        self.write_overrides().set_config(experiment, value, target);
+    }
+
+    pub fn remove_gate_override(&self, gate: &str, target: &OverrideTarget) {
+        self.write_overrides().remove_gate(gate, target);
+    }
+
+    pub fn remove_config_override(&self, config: &str, target: &OverrideTarget) {
+        self.write_overrides().remove_config(config, target);
+    }
+
+    pub fn remove_experiment_override(&self, experiment: &str, target: &OverrideTarget) {
+        self.write_overrides().remove_config(experiment, target);
+    }
+
+    pub fn remove_all_overrides(&self) {
+        self.write_overrides().clear();
+    }
+}
+
+fn validate_user(user: &StatsigUser) -> Result<()> {
+    if user.user_id.is_empty() {
+        return Err(StatsigError::InvalidUser("missing user id".to_string()));
+    }
+    Ok(())
 }
 
 // Private methods
 impl Client {
-    /// Logs experiment exposure asynchronously without blocking or returning errors.
+    fn read_overrides(&self) -> ShardedLockReadGuard<'_, Overrides> {
+        self.overrides
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_overrides(&self) -> ShardedLockWriteGuard<'_, Overrides> {
+        self.overrides
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn evaluation_details(&self, reason: EvaluationReason) -> EvaluationDetails {
+        EvaluationDetails {
+            reason,
+            config_sync_time: self.evaluator.config_sync_time(),
+            init_time: self.evaluator.init_time(),
+        }
+    }
+
+    /// Details of a local evaluation, with the sync time of the specs it used.
+    fn result_details(&self, res: &EvalResult) -> EvaluationDetails {
+        EvaluationDetails {
+            reason: res.reason,
+            config_sync_time: res.config_sync_time,
+            init_time: self.evaluator.init_time(),
+        }
+    }
+
+    async fn get_feature_gate_from_network(
+        &self,
+        gate: String,
+        user: StatsigUser,
+    ) -> Result<FeatureGate> {
+        let value = self.http_client.check_gate(gate.clone(), user).await?;
+        Ok(FeatureGate {
+            name: gate,
+            value,
+            rule_id: String::new(),
+            group_name: None,
+            evaluation_details: self.evaluation_details(EvaluationReason::Network),
+        })
+    }
+
+    async fn get_config_from_network<T: DeserializeOwned>(
+        &self,
+        config: String,
+        user: StatsigUser,
+    ) -> Result<StatsigConfig<T>> {
+        let config: StatsigConfig<T> = self.http_client.get_config(config, user).await?;
+        Ok(StatsigConfig {
+            evaluation_details: Some(self.evaluation_details(EvaluationReason::Network)),
+            ..config
+        })
+    }
+
+    async fn get_experiment_from_network<T: DeserializeOwned>(
+        &self,
+        experiment_name: String,
+        user: StatsigUser,
+    ) -> Result<StatsigExperiment<T>> {
+        let config: StatsigConfig<T> = self.get_config_from_network(experiment_name, user).await?;
+        Ok(StatsigExperiment {
+            value: config.value,
+            name: config.name,
+            group_name: config.group_name,
+            rule_id: config.rule_id,
+            group: config.group,
+            secondary_exposures: vec![],
+            evaluation_details: config.evaluation_details,
+        })
+    }
+
+    fn config_override(&self, config: &str, user: &StatsigUser) -> Option<serde_json::Value> {
+        self.read_overrides().config(config, user).cloned()
+    }
+
+    /// Logs experiment exposure in the background without blocking or returning errors.
     /// Errors are logged but not propagated.
-    async fn log_experiment_exposure_async(
-        self: Arc<Self>,
+    fn log_experiment_exposure(
+        &self,
         experiment_name: String,
         user: StatsigUser,
         group: String,
@@ -279,14 +713,17 @@ impl Client {
             }),
         };
 
-        if let Err(e) = self.http_client.log_custom_exposure(&post).await {
-            event!(
-                Level::ERROR,
-                "Failed to log experiment exposure for {}: {}",
-                experiment_name,
-                e
-            );
-        }
+        let http_client = self.http_client.clone();
+        self.logger.runtime.spawn(async move {
+            if let Err(e) = http_client.log_custom_exposure(&post).await {
+                event!(
+                    Level::ERROR,
+                    "Failed to log experiment exposure for {}: {}",
+                    experiment_name,
+                    e
+                );
+            }
+        });
     }
 
     async fn poll_for_changes(self: Arc<Self>, config_sync_interval: Option<Duration>) {
@@ -305,51 +742,65 @@ impl Client {
             if new_state.has_updates {
                 event!(Level::DEBUG, "Statsig state has changed");
                 self.evaluator.refresh_configs(new_state);
+                self.clear_eval_cache();
             }
         }
     }
 
-    async fn background_logs_flush(self: Arc<Self>) {
-        let mut interval = time::interval(Duration::from_secs(60));
+    async fn poll_for_id_list_changes(self: Arc<Self>, config_sync_interval: Option<Duration>) {
+        let mut interval =
+            time::interval(config_sync_interval.unwrap_or_else(|| Duration::from_secs(20)));
+        // The first tick completes immediately and the lists were just synced on creation
+        interval.tick().await;
         loop {
-            // TODO: Graceful shutdown, listen for signals, flush logs before exiting
             interval.tick().await;
-            event!(Level::DEBUG, "Flushing logs");
+            event!(Level::DEBUG, "Refreshing statsig id lists");
+            if let Err(e) = self
+                .http_client
+                .sync_id_lists(self.evaluator.id_lists())
+                .await
+            {
+                event!(Level::ERROR, "Failed to sync id lists: {}", e);
+            }
+            // Segments may have gained or lost users, even when the sync failed halfway
+            self.clear_eval_cache();
+        }
+    }
 
-            self.clone().flush_logs().await;
+    /// Evaluates locally, going through the evaluation cache when it's enabled.
+    fn evaluate(
+        &self,
+        kind: SpecKind,
+        name: &str,
+        user: &StatsigUser,
+        eval: impl FnOnce(&Evaluator, &StatsigUser, &str) -> EvalResult,
+    ) -> EvalResult {
+        match &self.eval_cache {
+            Some(cache) => {
+                cache.get_or_insert_with(kind, name, user, || eval(&self.evaluator, user, name))
+            }
+            None => eval(&self.evaluator, user, name),
         }
     }
 
-    async fn flush_logs(self: Arc<Self>) {
-        let events;
-        {
-            let mut logs = self
-                .event_logs
-                .lock()
-                .expect("should always be able to acquire lock");
-            events = std::mem::take(&mut *logs);
+    fn clear_eval_cache(&self) {
+        if let Some(cache) = &self.eval_cache {
+            cache.clear();
         }
+    }
 
-        if !events.is_empty() {
-            match self
-                .http_client
-                .log_event_internal(StatsigPost { events })
-                .await
-            {
-                Ok(_) => (),
-                Err(e) => {
-                    event!(Level::ERROR, "Failed to log events: {}", e);
-                }
-            };
+    async fn background_logs_flush(self: Arc<Self>) {
+        let mut interval = time::interval(Duration::from_secs(60));
+        loop {
+            // TODO: Graceful shutdown, listen for signals, flush logs before exiting
+            interval.tick().await;
+            event!(Level::DEBUG, "Flushing logs");
+
+            self.flush().await;
         }
     }
 
-    fn log_gate_exposure(
-        self: Arc<Self>,
-        gate: String,
-        user: StatsigUser,
-        eval_result: EvalResult,
-    ) {
+    fn log_gate_exposure(&self, gate: &str, user: &StatsigUser, eval_result: EvalResult) {
         let event = StatsigEvent {
             event_name: GATE_EXPOSURE_EVENT.to_string(),
             value: eval_result.pass.to_string(),
@@ -358,29 +809,20 @@ impl Client {
                 .unwrap_or_else(|_| Duration::from_secs(0))
                 .as_secs()
                 .to_string(),
-            user,
+            user: user.clone(),
             metadata: HashMap::from([
-                ("gate".to_string(), gate),
+                ("gate".to_string(), gate.to_string()),
                 ("gateValue".to_string(), eval_result.pass.to_string()),
                 ("ruleID".to_string(), eval_result.id),
             ]),
         };
-        let mut events = self
-            .event_logs
-            .lock()
-            .expect("should always be able to acquire lock");
-        events.push(event);
-        if events.len() >= MAX_LOG_EVENTS {
-            tokio::spawn(self.clone().flush_logs());
-        }
+        self.logger.push(ExposureEvent {
+            event,
+            secondary_exposures: eval_result.secondary_exposures,
+        });
     }
 
-    fn log_config_exposure(
-        self: Arc<Self>,
-        config: String,
-        user: StatsigUser,
-        eval_result: EvalResult,
-    ) {
+    fn log_config_exposure(&self, config: &str, user: &StatsigUser, eval_result: EvalResult) {
         let event = StatsigEvent {
             event_name: CONFIG_EXPOSURE_EVENT.to_string(),
             value: eval_result.pass.to_string(),
@@ -389,19 +831,81 @@ impl Client {
                 .unwrap_or_else(|_| Duration::from_secs(0))
                 .as_secs()
                 .to_string(),
-            user,
+            user: user.clone(),
             metadata: HashMap::from([
-                ("config".to_string(), config),
+                ("config".to_string(), config.to_string()),
                 ("ruleID".to_string(), eval_result.id),
             ]),
         };
-        let mut events = self
-            .event_logs
-            .lock()
-            .expect("should always be able to acquire lock");
-        events.push(event);
-        if events.len() >= MAX_LOG_EVENTS {
-            tokio::spawn(self.clone().flush_logs());
-        }
+        self.logger.push(ExposureEvent {
+            event,
+            secondary_exposures: eval_result.secondary_exposures,
+        });
+    }
+
+    /// Returns the logger the layer calls with each parameter that's read. Only the parameters
+    /// of the experiment the layer delegated to are attributed to it.
+    fn layer_exposure_logger(
+        &self,
+        layer: &str,
+        user: &StatsigUser,
+        eval_result: EvalResult,
+    ) -> ParameterExposureLogger {
+        let logger = self.logger.clone();
+        let layer = layer.to_string();
+        let user = user.clone();
+        let EvalResult {
+            pass,
+            rule_id,
+            secondary_exposures,
+            undelegated_secondary_exposures,
+            config_delegate,
+            explicit_parameters,
+            ..
+        } = eval_result;
+        let undelegated_secondary_exposures =
+            undelegated_secondary_exposures.unwrap_or_else(|| secondary_exposures.clone());
+        let explicit_parameters = explicit_parameters.unwrap_or_default();
+        ParameterExposureLogger(Arc::new(move |parameter: &str| {
+            let is_explicit = explicit_parameters.contains_key(parameter);
+            let (allocated_experiment, secondary_exposures) = if is_explicit {
+                (
+                    config_delegate.clone().unwrap_or_default(),
+                    secondary_exposures.clone(),
+                )
+            } else {
+                (String::new(), undelegated_secondary_exposures.clone())
+            };
+            let event = StatsigEvent {
+                event_name: LAYER_EXPOSURE_EVENT.to_string(),
+                value: pass.to_string(),
+                time: SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_else(|_| Duration::from_secs(0))
+                    .as_secs()
+                    .to_string(),
+                user: user.clone(),
+                metadata: HashMap::from([
+                    ("config".to_string(), layer.clone()),
+                    ("ruleID".to_string(), rule_id.clone()),
+                    ("allocatedExperiment".to_string(), allocated_experiment),
+                    ("parameterName".to_string(), parameter.to_string()),
+                    ("isExplicitParameter".to_string(), is_explicit.to_string()),
+                ]),
+            };
+            logger.push(ExposureEvent {
+                event,
+                secondary_exposures,
+            });
+        }))
+    }
+}
+
+async fn post_logs(http_client: &StatsigHttpClient, events: Vec<ExposureEvent>) {
+    if events.is_empty() {
+        return;
+    }
+    if let Err(e) = http_client.log_exposures(&events).await {
+        event!(Level::ERROR, "Failed to log events: {}", e);
     }
 }
@@ -0,0 +1,118 @@
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+pub type Result<T, E = StatsigError> = std::result::Result<T, E>;
+
+/// Errors returned by the client.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum StatsigError {
+    /// The user can't be checked, like when it has no user id.
+    #[error("statsig: invalid user: {0}")]
+    InvalidUser(String),
+    /// The config isn't in the synced specs.
+    #[error("statsig: unknown spec {0}")]
+    UnknownSpec(String),
+    /// A value doesn't match the type it's deserialized into. The path is where in the value
+    /// it failed, like `items[0].name`, or `.` for the value itself.
+    #[error("statsig: failed to deserialize {path}: {source}")]
+    Deserialization {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The statsig API answered a request with an unexpected status.
+    #[error("statsig: {endpoint} returned status {status}")]
+    HttpStatus { endpoint: &'static str, status: u16 },
+    /// A request couldn't be sent, or its response couldn't be read.
+    #[error("statsig: request to {endpoint} failed: {source}")]
+    Transport {
+        endpoint: &'static str,
+        #[source]
+        source: reqwest::Error,
+    },
+    /// A request took longer than the timeout, which can be changed with the
+    /// STATSIG_TIMEOUT_MS environment variable.
+    #[error("statsig: request to {endpoint} timed out")]
+    Timeout { endpoint: &'static str },
+    /// There are no specs to evaluate with locally, because the cache is disabled or they
+    /// weren't synced yet.
+    #[error("statsig: no specs to evaluate with locally")]
+    Uninitialized,
+    /// One of the `StatsigOptions` can't be used, like a geoip database that can't be read.
+    #[error("statsig: invalid option: {0}")]
+    InvalidOption(String),
+}
+
+impl StatsigError {
+    /// Splits timeouts from the other failures of a request.
+    pub(crate) fn from_request(endpoint: &'static str, source: reqwest::Error) -> Self {
+        if source.is_timeout() {
+            StatsigError::Timeout { endpoint }
+        } else {
+            StatsigError::Transport { endpoint, source }
+        }
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for StatsigError {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        StatsigError::Deserialization {
+            path: e.path().to_string(),
+            source: e.into_inner(),
+        }
+    }
+}
+
+/// Like `serde_json::from_value`, with the path of the failing field in the error.
+pub(crate) fn from_value<T: DeserializeOwned>(value: serde_json::Value) -> Result<T> {
+    Ok(serde_path_to_error::deserialize(value)?)
+}
+
+/// Like `serde_json::from_slice`, with the path of the failing field in the error.
+pub(crate) fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let value = serde_path_to_error::deserialize(&mut deserializer)?;
+    deserializer
+        .end()
+        .map_err(|source| StatsigError::Deserialization {
+            path: ".".to_string(),
+            source,
+        })?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Item {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Items {
+        #[allow(dead_code)]
+        items: Vec<Item>,
+    }
+
+    #[test]
+    fn test_deserialization_path() {
+        let value = json!({"items": [{"name": "a"}, {"name": 1}]});
+        match from_value::<Items>(value) {
+            Err(StatsigError::Deserialization { path, .. }) => assert_eq!(path, "items[1].name"),
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        match from_slice::<Items>(br#"{"items": [{"name": "a"}]} trailing"#) {
+            Err(StatsigError::Deserialization { path, .. }) => assert_eq!(path, "."),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(from_slice::<Items>(br#"{"items": []}"#).is_ok());
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    num::NonZeroUsize,
+    sync::{Mutex, PoisonError},
+};
+
+use lru::LruCache;
+
+use crate::{evaluator::models::EvalResult, models::StatsigUser};
+
+/// What a cached result was evaluated for, a gate and a config can share a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpecKind {
+    Gate,
+    DynamicConfig,
+    Layer,
+}
+
+struct Entry {
+    kind: SpecKind,
+    name: String,
+    user: StatsigUser,
+    result: EvalResult,
+}
+
+struct Inner {
+    entries: LruCache<u64, Entry>,
+    /// Bumped on every clear, so an evaluation that raced with it isn't stored.
+    generation: u64,
+}
+
+/// Bounded cache of local evaluation results, keyed by a hash of the spec and the user.
+pub struct EvalCache {
+    inner: Mutex<Inner>,
+}
+
+impl EvalCache {
+    /// Creates a cache holding up to `capacity` results, `None` when the capacity is 0.
+    pub fn new(capacity: usize) -> Option<Self> {
+        let capacity = NonZeroUsize::new(capacity)?;
+        Some(Self {
+            inner: Mutex::new(Inner {
+                entries: LruCache::new(capacity),
+                generation: 0,
+            }),
+        })
+    }
+
+    /// Returns the cached result for the spec and user, or evaluates and caches it.
+    /// Results that have to be fetched from the server or depend on the current time are never
+    /// cached.
+    pub fn get_or_insert_with(
+        &self,
+        kind: SpecKind,
+        name: &str,
+        user: &StatsigUser,
+        eval: impl FnOnce() -> EvalResult,
+    ) -> EvalResult {
+        let key = key(kind, name, user);
+        let generation = {
+            let mut inner = self.lock();
+            match inner.entries.get(&key) {
+                // The key is only a hash, so make sure it's really the same evaluation
+                Some(entry) if entry.kind == kind && entry.name == name && entry.user == *user => {
+                    return entry.result.clone();
+                }
+                _ => inner.generation,
+            }
+        };
+
+        // Evaluate without holding the lock, concurrent misses just evaluate twice
+        let result = eval();
+        if !result.fetch_from_server && !result.time_dependent {
+            let mut inner = self.lock();
+            if inner.generation == generation {
+                inner.entries.put(
+                    key,
+                    Entry {
+                        kind,
+                        name: name.to_string(),
+                        user: user.clone(),
+                        result: result.clone(),
+                    },
+                );
+            }
+        }
+        result
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.entries.clear();
+        inner.generation += 1;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn key(kind: SpecKind, name: &str, user: &StatsigUser) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    kind.hash(&mut hasher);
+    name.hash(&mut hasher);
+    hash_user(user, &mut hasher);
+    hasher.finish()
+}
+
+/// Hashes every field of the user, maps are hashed in key order so equal users hash the same.
+fn hash_user(user: &StatsigUser, state: &mut impl Hasher) {
+    user.user_id.hash(state);
+    user.email.hash(state);
+    user.ip.hash(state);
+    user.user_agent.hash(state);
+    user.country.hash(state);
+    user.locale.hash(state);
+    user.app_version.hash(state);
+    for values in [&user.custom, &user.private_atributes, &user.custom_ids] {
+        hash_sorted(values.iter().flatten(), state, |value, state| {
+            value.hash(state)
+        });
+    }
+    hash_sorted(user.custom_values.iter().flatten(), state, hash_value);
+    user.statsig_environment.tier.hash(state);
+}
+
+fn hash_sorted<'a, T: 'a, H: Hasher>(
+    entries: impl Iterator<Item = (&'a String, &'a T)>,
+    state: &mut H,
+    hash_entry: impl Fn(&T, &mut H),
+) {
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    entries.len().hash(state);
+    for (key, value) in entries {
+        key.hash(state);
+        hash_entry(value, state);
+    }
+}
+
+fn hash_value<H: Hasher>(value: &serde_json::Value, state: &mut H) {
+    std::mem::discriminant(value).hash(state);
+    match value {
+        serde_json::Value::Null => {}
+        serde_json::Value::Bool(b) => b.hash(state),
+        serde_json::Value::Number(n) => n.hash(state),
+        serde_json::Value::String(s) => s.hash(state),
+        serde_json::Value::Array(values) => {
+            values.len().hash(state);
+            for value in values {
+                hash_value(value, state);
+            }
+        }
+        serde_json::Value::Object(map) => hash_sorted(map.iter(), state, hash_value),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, collections::HashMap};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn user() -> StatsigUser {
+        StatsigUser {
+            custom_values: Some(HashMap::from([
+                ("a".to_string(), json!(1)),
+                ("b".to_string(), json!({"x": [1, "2"], "y": null})),
+                ("c".to_string(), json!(true)),
+            ])),
+            ..StatsigUser::new("user_id".to_string(), "production".to_string())
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let u = user();
+        let mut reordered = user();
+        // A map built in another order, with another capacity and seed, iterates in another order
+        let mut entries: Vec<_> = u.custom_values.clone().unwrap().into_iter().collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+        let mut custom_values = HashMap::with_capacity(64);
+        custom_values.extend(entries);
+        reordered.custom_values = Some(custom_values);
+        assert_eq!(
+            key(SpecKind::Gate, "gate", &u),
+            key(SpecKind::Gate, "gate", &reordered)
+        );
+
+        let mut changed = user();
+        changed
+            .custom_values
+            .as_mut()
+            .unwrap()
+            .insert("c".to_string(), json!(false));
+        assert_ne!(
+            key(SpecKind::Gate, "gate", &u),
+            key(SpecKind::Gate, "gate", &changed)
+        );
+        assert_ne!(
+            key(SpecKind::Gate, "gate", &u),
+            key(SpecKind::DynamicConfig, "gate", &u)
+        );
+        assert_ne!(
+            key(SpecKind::Gate, "gate", &u),
+            key(SpecKind::Gate, "other_gate", &u)
+        );
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let cache = EvalCache::new(2).unwrap();
+        let evals = &Cell::new(0);
+        let eval = |result: fn() -> EvalResult| {
+            move || {
+                evals.set(evals.get() + 1);
+                result()
+            }
+        };
+
+        let u = user();
+        assert!(
+            cache
+                .get_or_insert_with(SpecKind::Gate, "gate", &u, eval(EvalResult::pass))
+                .pass
+        );
+        assert!(
+            cache
+                .get_or_insert_with(SpecKind::Gate, "gate", &u, eval(EvalResult::fail))
+                .pass
+        );
+        assert_eq!(evals.get(), 1);
+
+        // Results that need the server are evaluated every time
+        for _ in 0..2 {
+            cache.get_or_insert_with(
+                SpecKind::Layer,
+                "layer",
+                &u,
+                eval(EvalResult::fetch_from_server),
+            );
+        }
+        assert_eq!(evals.get(), 3);
+
+        cache.clear();
+        assert!(
+            !cache
+                .get_or_insert_with(SpecKind::Gate, "gate", &u, eval(EvalResult::fail))
+                .pass
+        );
+        assert_eq!(evals.get(), 4);
+
+        // A clear while evaluating keeps the stale result out of the cache
+        cache.clear();
+        cache.get_or_insert_with(SpecKind::Gate, "gate", &u, || {
+            cache.clear();
+            EvalResult::pass()
+        });
+        assert!(
+            !cache
+                .get_or_insert_with(SpecKind::Gate, "gate", &u, eval(EvalResult::fail))
+                .pass
+        );
+        assert_eq!(evals.get(), 5);
+
+        // Least recently used results are evicted past the capacity
+        cache.get_or_insert_with(SpecKind::Gate, "a", &u, eval(EvalResult::pass));
+        cache.get_or_insert_with(SpecKind::Gate, "b", &u, eval(EvalResult::pass));
+        cache.get_or_insert_with(SpecKind::Gate, "gate", &u, eval(EvalResult::pass));
+        assert_eq!(evals.get(), 8);
+
+        // So are results that depend on the current time
+        for _ in 0..2 {
+            cache.get_or_insert_with(
+                SpecKind::DynamicConfig,
+                "config",
+                &u,
+                eval(|| EvalResult {
+                    time_dependent: true,
+                    ..EvalResult::pass()
+                }),
+            );
+        }
+        assert_eq!(evals.get(), 10);
+
+        assert!(EvalCache::new(0).is_none());
+    }
+}
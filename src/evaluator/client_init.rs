@@ -0,0 +1,247 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::models::{
+    ClientInitializeResponse, ClientInitializeResponseOptions, ConfigInitializeValue,
+    GateInitializeValue, HashAlgorithm, LayerInitializeValue, StatsigUser,
+};
+
+use super::{
+    is_holdout_or_segment,
+    models::{ConfigSpec, EntityType},
+    Evaluator,
+};
+
+/// djb2 over the UTF-16 code units of the string, like the javascript client SDK computes it.
+fn djb2(s: &str) -> u32 {
+    s.encode_utf16().fold(0u32, |hash, c| {
+        (hash << 5).wrapping_sub(hash).wrapping_add(c as u32)
+    })
+}
+
+/// Hashes a spec name the way the client SDKs look it up.
+fn hash_name(name: &str, algorithm: HashAlgorithm) -> String {
+    match algorithm {
+        HashAlgorithm::Sha256 => STANDARD.encode(Sha256::digest(name.as_bytes())),
+        HashAlgorithm::Djb2 => djb2(name).to_string(),
+        HashAlgorithm::None => name.to_string(),
+    }
+}
+
+fn is_experiment(spec: &ConfigSpec) -> bool {
+    spec.entity == Some(EntityType::Experiment)
+}
+
+/// Specs bucketed by stable id are evaluated per device rather than per user.
+fn is_device_based(spec: &ConfigSpec) -> bool {
+    spec.id_type
+        .as_deref()
+        .is_some_and(|id_type| id_type.eq_ignore_ascii_case("stableID"))
+}
+
+impl Evaluator {
+    /// Evaluates every spec a client SDK can use into an `/initialize` response.
+    ///
+    /// Holdouts and segments are left out, like specs that can only be evaluated by the API.
+    pub fn get_client_initialize_response_internal(
+        &self,
+        user: &StatsigUser,
+        options: &ClientInitializeResponseOptions,
+    ) -> ClientInitializeResponse {
+        let specs = self.specs.load_full();
+        let hash = |name: &str| hash_name(name, options.hash);
+
+        let feature_gates = specs
+            .gates
+            .iter()
+            .filter(|spec| !is_holdout_or_segment(&spec.spec))
+            .filter_map(|compiled| {
+                let res = self.eval_spec(&specs, user, compiled);
+                let (name, spec) = (&compiled.spec.name, &compiled.spec);
+                if res.fetch_from_server {
+                    return None;
+                }
+                let value = GateInitializeValue {
+                    name: hash(name),
+                    value: res.pass,
+                    rule_id: res.rule_id,
+                    id_type: spec.id_type.clone(),
+                    secondary_exposures: res.secondary_exposures,
+                };
+                Some((hash(name), value))
+            })
+            .collect();
+
+        let dynamic_configs = specs
+            .dynamic_configs
+            .iter()
+            .filter_map(|compiled| {
+                let res = self.eval_spec(&specs, user, compiled);
+                let (name, spec) = (&compiled.spec.name, &compiled.spec);
+                if res.fetch_from_server {
+                    return None;
+                }
+                let experiment = is_experiment(spec);
+                let value = ConfigInitializeValue {
+                    name: hash(name),
+                    value: res.config_value.unwrap_or_else(|| serde_json::json!({})),
+                    rule_id: res.rule_id,
+                    group: res.group,
+                    group_name: res.group_name,
+                    is_device_based: is_device_based(spec),
+                    id_type: spec.id_type.clone(),
+                    secondary_exposures: res.secondary_exposures,
+                    is_user_in_experiment: experiment.then_some(res.is_experiment_group),
+                    is_experiment_active: experiment.then(|| spec.is_active.unwrap_or(false)),
+                    explicit_parameters: if experiment {
+                        Some(spec.explicit_parameters.clone().unwrap_or_default())
+                    } else {
+                        None
+                    },
+                };
+                Some((hash(name), value))
+            })
+            .collect();
+
+        let layer_configs = specs
+            .layer_configs
+            .iter()
+            .filter_map(|compiled| {
+                let res = self.eval_spec(&specs, user, compiled);
+                let (name, spec) = (&compiled.spec.name, &compiled.spec);
+                if res.fetch_from_server {
+                    return None;
+                }
+                let delegate = res
+                    .config_delegate
+                    .as_ref()
+                    .and_then(|delegate| specs.dynamic_configs.get(delegate));
+                let value = LayerInitializeValue {
+                    name: hash(name),
+                    value: res.config_value.unwrap_or_else(|| serde_json::json!({})),
+                    rule_id: res.rule_id,
+                    group: res.group,
+                    group_name: res.group_name,
+                    is_device_based: is_device_based(spec),
+                    undelegated_secondary_exposures: res
+                        .undelegated_secondary_exposures
+                        .unwrap_or_else(|| res.secondary_exposures.clone()),
+                    secondary_exposures: res.secondary_exposures,
+                    // In the order the experiment lists them
+                    explicit_parameters: delegate
+                        .and_then(|d| d.spec.explicit_parameters.clone())
+                        .unwrap_or_default(),
+                    allocated_experiment_name: res.config_delegate.as_deref().map(hash),
+                    is_user_in_experiment: delegate.map(|_| res.is_experiment_group),
+                    is_experiment_active: delegate.map(|d| d.spec.is_active.unwrap_or(false)),
+                };
+                Some((hash(name), value))
+            })
+            .collect();
+
+        ClientInitializeResponse {
+            feature_gates,
+            dynamic_configs,
+            layer_configs,
+            has_updates: true,
+            time: specs.sync_time,
+            hash_used: options.hash,
+            // The response is sent to the browser, private attributes stay on the server
+            user: StatsigUser {
+                private_atributes: None,
+                ..user.clone()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{djb2, hash_name};
+    use crate::{
+        evaluator::Evaluator,
+        models::{ClientInitializeResponseOptions, HashAlgorithm, StatsigUser},
+    };
+
+    #[test]
+    fn test_hash_name() {
+        assert_eq!(
+            hash_name("a_gate", HashAlgorithm::Sha256),
+            "5v6IDYah7WmooSLkL7W3ak4pzBq5KXvJdac3tRmLnzE="
+        );
+        assert_eq!(hash_name("a_gate", HashAlgorithm::None), "a_gate");
+        // Values computed with the javascript implementation
+        assert_eq!(hash_name("test_gate", HashAlgorithm::Djb2), "3114454104");
+        assert_eq!(
+            djb2("a_very_long_gate_name_for_overflow_\u{1F642}"),
+            4132313364
+        );
+    }
+
+    #[test]
+    fn test_device_based_and_explicit_parameters() {
+        let params = ["f", "b", "e", "a", "d", "c"];
+        let spec = |name: &str, id_type: &str, extra: serde_json::Value| {
+            let mut spec = json!({
+                "name": name,
+                "type": "dynamic_config",
+                "salt": "salt",
+                "enabled": true,
+                "defaultValue": {},
+                "idType": id_type,
+                "rules": [{
+                    "name": "rule",
+                    "id": format!("{}_rule", name),
+                    "salt": "rule_salt",
+                    "passPercentage": 100,
+                    "idType": id_type,
+                    "returnValue": {},
+                    "conditions": [{"type": "public", "idType": id_type}],
+                }],
+            });
+            for (key, value) in extra.as_object().unwrap() {
+                match key.as_str() {
+                    "configDelegate" => spec["rules"][0][key] = value.clone(),
+                    _ => spec[key] = value.clone(),
+                }
+            }
+            spec
+        };
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(
+            serde_json::from_value(json!({
+                "dynamic_configs": [
+                    spec("user_config", "userID", json!({})),
+                    spec("experiment", "stableID", json!({
+                        "entity": "experiment",
+                        "explicitParameters": params,
+                    })),
+                ],
+                "layer_configs": [
+                    spec("layer", "stableID", json!({"configDelegate": "experiment"})),
+                ],
+                "has_updates": true,
+            }))
+            .unwrap(),
+        );
+
+        let response = evaluator.get_client_initialize_response_internal(
+            &StatsigUser::new("user_id".to_string(), "production".to_string()),
+            &ClientInitializeResponseOptions {
+                hash: HashAlgorithm::None,
+            },
+        );
+        assert!(!response.dynamic_configs["user_config"].is_device_based);
+        let experiment = &response.dynamic_configs["experiment"];
+        assert!(experiment.is_device_based);
+        assert_eq!(
+            experiment.explicit_parameters,
+            Some(params.map(String::from).to_vec())
+        );
+        let layer = &response.layer_configs["layer"];
+        assert!(layer.is_device_based);
+        assert_eq!(layer.explicit_parameters, params.map(String::from).to_vec());
+    }
+}
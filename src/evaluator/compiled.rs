@@ -0,0 +1,669 @@
+use std::collections::{HashMap, HashSet};
+
+use regex::{Regex, RegexBuilder};
+use tracing::{event, Level};
+
+use super::getters::{get_numeric_value, get_string, get_unix_epoch_millis};
+use super::models::{ConditionType, ConfigCondition, ConfigRule, ConfigSpec, OperatorType};
+use super::version::Version;
+
+/// Upper bound for the compiled program of a `str_matches` pattern.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// Upper bound for the lazy DFA cache of a `str_matches` pattern.
+const REGEX_DFA_SIZE_LIMIT: usize = 1 << 20;
+
+/// Compiles a `str_matches` pattern, returning `None` if it is invalid or too big.
+fn compile_regex(pattern: &str) -> Option<Regex> {
+    match RegexBuilder::new(pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_DFA_SIZE_LIMIT)
+        .build()
+    {
+        Ok(re) => Some(re),
+        Err(e) => {
+            event!(
+                Level::WARN,
+                "Invalid str_matches pattern {:?}, treating as non-matching: {}",
+                pattern,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// The strings in a target array, non-string elements are converted like user values.
+fn target_strings<C: FromIterator<String>>(target: &serde_json::Value, lowercase: bool) -> C {
+    target
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(get_string)
+        .map(|s| if lowercase { s.to_ascii_lowercase() } else { s })
+        .collect()
+}
+
+/// A spec with everything the evaluation needs prepared up front.
+pub struct CompiledSpec {
+    /// The spec as synced, its rules are moved into `rules`.
+    pub spec: ConfigSpec,
+    pub rules: Vec<CompiledRule>,
+    /// Set on gates whose pass_gate/fail_gate conditions lead back to them, evaluating them
+    /// fails with an error.
+    pub cyclic: bool,
+    /// Set on layers, only their rules hand allocation to the experiment in `config_delegate`.
+    pub delegates: bool,
+    /// Set when the result can change with the current time, through a current_time condition
+    /// of the spec, of a gate or holdout it checks or of the experiment it delegates to.
+    pub time_dependent: bool,
+}
+
+impl CompiledSpec {
+    /// Indices of the gates checked by the spec's pass_gate/fail_gate conditions.
+    fn checked_gates(&self) -> impl Iterator<Item = usize> + '_ {
+        self.rules
+            .iter()
+            .flat_map(|rule| rule.conditions.iter())
+            .filter_map(|condition| condition.gate)
+    }
+
+    /// Indices of the holdouts checked by the spec's pass_gate/fail_gate conditions.
+    fn checked_holdouts(&self) -> impl Iterator<Item = usize> + '_ {
+        self.rules
+            .iter()
+            .flat_map(|rule| rule.conditions.iter())
+            .filter_map(|condition| condition.holdout)
+    }
+
+    /// Whether a gate or holdout the spec checks depends on the current time.
+    fn checks_time_dependent(&self, gates: &SpecMap, holdouts: &SpecMap) -> bool {
+        self.checked_gates()
+            .any(|gate| gates.specs[gate].time_dependent)
+            || self
+                .checked_holdouts()
+                .any(|holdout| holdouts.specs[holdout].time_dependent)
+    }
+}
+
+pub struct CompiledRule {
+    /// The rule as synced, its conditions are moved into `conditions`.
+    pub rule: ConfigRule,
+    /// `<spec salt>.<rule salt>.`, the unit id is appended to it to compute the bucket.
+    pub bucket_salt: String,
+    pub conditions: Vec<CompiledCondition>,
+}
+
+pub struct CompiledCondition {
+    pub condition: ConfigCondition,
+    /// The target value prepared for the operator.
+    pub target: Target,
+    /// Index in the gates `SpecMap` of the gate a pass_gate/fail_gate condition checks,
+    /// `None` when the gate doesn't exist.
+    pub gate: Option<usize>,
+    /// Index in the holdouts `SpecMap` of the holdout a pass_gate/fail_gate condition checks,
+    /// when there's no gate with that name.
+    pub holdout: Option<usize>,
+    /// `<salt>.` of a user_bucket condition, the unit id is appended to it.
+    pub bucket_salt: Option<String>,
+}
+
+/// The target value of a condition, parsed according to its operator.
+pub enum Target {
+    /// Compared as is, for operators that look at the type of both sides.
+    Value(serde_json::Value),
+    Number(Option<f64>),
+    Version(Option<Version>),
+    /// Milliseconds since the unix epoch, `None` when it overflows.
+    Timestamp(Option<i64>),
+    /// Strings for `any`/`none`, lowercased unless the operator is case sensitive.
+    Set(HashSet<String>),
+    /// Lowercased strings for the `str_*_any`/`str_contains_none` operators.
+    Strings(Vec<String>),
+    /// `None` when the `str_matches` pattern is invalid.
+    Regex(Option<Regex>),
+}
+
+/// Compiled specs by name, gates are also addressed by index from pass_gate/fail_gate.
+#[derive(Default)]
+pub struct SpecMap {
+    specs: Vec<CompiledSpec>,
+    indices: HashMap<String, usize>,
+}
+
+impl SpecMap {
+    pub fn get(&self, name: &str) -> Option<&CompiledSpec> {
+        self.indices.get(name).map(|i| &self.specs[*i])
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<&CompiledSpec> {
+        self.specs.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CompiledSpec> {
+        self.specs.iter()
+    }
+
+    /// Flags the gates that check each other in a cycle, returning the cycles found by name.
+    ///
+    /// Every cycle gets at least one of its gates flagged, which is enough for an evaluation
+    /// to stop there instead of going around forever.
+    pub fn mark_cycles(&mut self) -> Vec<Vec<String>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            New,
+            Visiting,
+            Done,
+        }
+
+        let edges: Vec<Vec<usize>> = self
+            .specs
+            .iter()
+            .map(|spec| spec.checked_gates().collect())
+            .collect();
+        let mut state = vec![State::New; edges.len()];
+        let mut cycles = vec![];
+        for root in 0..edges.len() {
+            if state[root] != State::New {
+                continue;
+            }
+            // Depth first, keeping the gates on the current path with the next edge to follow
+            state[root] = State::Visiting;
+            let mut path = vec![(root, 0)];
+            while let Some(&(gate, edge)) = path.last() {
+                let next = match edges[gate].get(edge) {
+                    None => {
+                        state[gate] = State::Done;
+                        path.pop();
+                        continue;
+                    }
+                    Some(&next) => next,
+                };
+                let last = path.len() - 1;
+                path[last].1 += 1;
+                match state[next] {
+                    State::New => {
+                        state[next] = State::Visiting;
+                        path.push((next, 0));
+                    }
+                    State::Visiting => {
+                        let start = path.iter().position(|(g, _)| *g == next).unwrap_or(0);
+                        let mut cycle: Vec<String> = path[start..]
+                            .iter()
+                            .map(|(g, _)| {
+                                self.specs[*g].cyclic = true;
+                                self.specs[*g].spec.name.clone()
+                            })
+                            .collect();
+                        cycle.push(self.specs[next].spec.name.clone());
+                        cycles.push(cycle);
+                    }
+                    State::Done => {}
+                }
+            }
+        }
+        cycles
+    }
+
+    /// Flags the gates and holdouts that check a time dependent gate or holdout, directly or
+    /// through others.
+    pub fn mark_time_dependent_gates(gates: &mut SpecMap, holdouts: &mut SpecMap) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for in_holdouts in [false, true] {
+                let len = if in_holdouts {
+                    holdouts.specs.len()
+                } else {
+                    gates.specs.len()
+                };
+                for i in 0..len {
+                    let spec = if in_holdouts {
+                        &holdouts.specs[i]
+                    } else {
+                        &gates.specs[i]
+                    };
+                    if spec.time_dependent || !spec.checks_time_dependent(gates, holdouts) {
+                        continue;
+                    }
+                    let map = if in_holdouts {
+                        &mut *holdouts
+                    } else {
+                        &mut *gates
+                    };
+                    map.specs[i].time_dependent = true;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Flags the specs that check a time dependent gate or holdout, or delegate to a time
+    /// dependent experiment of `experiments`.
+    pub fn mark_time_dependent(
+        &mut self,
+        gates: &SpecMap,
+        holdouts: &SpecMap,
+        experiments: &SpecMap,
+    ) {
+        for spec in self.specs.iter_mut() {
+            let delegates_to_time_dependent = || {
+                spec.delegates
+                    && spec.rules.iter().any(|rule| {
+                        rule.rule
+                            .config_delegate
+                            .as_ref()
+                            .and_then(|name| experiments.get(name))
+                            .is_some_and(|experiment| experiment.time_dependent)
+                    })
+            };
+            if spec.checks_time_dependent(gates, holdouts) || delegates_to_time_dependent() {
+                spec.time_dependent = true;
+            }
+        }
+    }
+}
+
+/// Turns synced specs into their compiled form.
+pub struct Compiler {
+    strict_versions: bool,
+    gate_indices: HashMap<String, usize>,
+    holdout_indices: HashMap<String, usize>,
+    /// Patterns already compiled, so specs sharing one compile it once.
+    regexes: HashMap<String, Option<Regex>>,
+}
+
+impl Compiler {
+    /// `gates` are the gates that will be compiled, in order, pass_gate/fail_gate conditions
+    /// are resolved against them.
+    pub fn new<'a>(strict_versions: bool, gates: impl Iterator<Item = &'a ConfigSpec>) -> Self {
+        let mut gate_indices = HashMap::new();
+        for gate in gates {
+            let index = gate_indices.len();
+            gate_indices.entry(gate.name.clone()).or_insert(index);
+        }
+        Self {
+            strict_versions,
+            gate_indices,
+            holdout_indices: HashMap::new(),
+            regexes: HashMap::new(),
+        }
+    }
+
+    /// `holdouts` are the holdouts that will be compiled, in order, pass_gate/fail_gate
+    /// conditions on a name that isn't a gate are resolved against them.
+    pub fn with_holdouts<'a>(mut self, holdouts: impl Iterator<Item = &'a ConfigSpec>) -> Self {
+        for holdout in holdouts {
+            let index = self.holdout_indices.len();
+            self.holdout_indices
+                .entry(holdout.name.clone())
+                .or_insert(index);
+        }
+        self
+    }
+
+    /// Compiles the specs, the last one wins when a name is repeated.
+    pub fn compile_specs(&mut self, specs: impl IntoIterator<Item = ConfigSpec>) -> SpecMap {
+        let mut map = SpecMap::default();
+        for spec in specs {
+            let compiled = self.compile_spec(spec);
+            match map.indices.get(&compiled.spec.name) {
+                Some(i) => map.specs[*i] = compiled,
+                None => {
+                    map.indices
+                        .insert(compiled.spec.name.clone(), map.specs.len());
+                    map.specs.push(compiled);
+                }
+            }
+        }
+        map
+    }
+
+    /// Compiles layers, whose rules can delegate to the experiments compiled with
+    /// `compile_specs`.
+    pub fn compile_layers(&mut self, specs: impl IntoIterator<Item = ConfigSpec>) -> SpecMap {
+        let mut map = self.compile_specs(specs);
+        for layer in map.specs.iter_mut() {
+            layer.delegates = true;
+        }
+        map
+    }
+
+    fn compile_spec(&mut self, mut spec: ConfigSpec) -> CompiledSpec {
+        let rules: Vec<CompiledRule> = spec
+            .rules
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|mut rule| {
+                let rule_salt = if rule.salt.is_empty() {
+                    &rule.id
+                } else {
+                    &rule.salt
+                };
+                let bucket_salt = format!("{}.{}.", spec.salt, rule_salt);
+                let conditions = std::mem::take(&mut rule.conditions)
+                    .into_iter()
+                    .map(|condition| self.compile_condition(condition))
+                    .collect();
+                CompiledRule {
+                    rule,
+                    bucket_salt,
+                    conditions,
+                }
+            })
+            .collect();
+        let time_dependent = rules.iter().any(|rule| {
+            rule.conditions
+                .iter()
+                .any(|condition| condition.condition.r#type == ConditionType::CurrentTime)
+        });
+        CompiledSpec {
+            spec,
+            rules,
+            cyclic: false,
+            delegates: false,
+            time_dependent,
+        }
+    }
+
+    pub fn compile_condition(&mut self, condition: ConfigCondition) -> CompiledCondition {
+        let target_gate = match condition.r#type {
+            ConditionType::PassGate | ConditionType::FailGate => {
+                condition.target_value.as_ref().and_then(|t| t.as_str())
+            }
+            _ => None,
+        };
+        let gate = target_gate.and_then(|name| self.gate_indices.get(name).copied());
+        let holdout = match gate {
+            Some(_) => None,
+            None => target_gate.and_then(|name| self.holdout_indices.get(name).copied()),
+        };
+        let bucket_salt = match condition.r#type {
+            ConditionType::UserBucket => condition
+                .additional_values
+                .as_ref()
+                .and_then(|values| values.get("salt"))
+                .map(|salt| format!("{}.", salt)),
+            _ => None,
+        };
+        CompiledCondition {
+            target: self.compile_target(&condition),
+            gate,
+            holdout,
+            bucket_salt,
+            condition,
+        }
+    }
+
+    fn compile_target(&mut self, condition: &ConfigCondition) -> Target {
+        let target = condition
+            .target_value
+            .clone()
+            .unwrap_or(serde_json::Value::Null);
+        match condition.operator {
+            Some(OperatorType::Gt)
+            | Some(OperatorType::Gte)
+            | Some(OperatorType::Lt)
+            | Some(OperatorType::Lte) => Target::Number(get_numeric_value(&target)),
+            Some(OperatorType::VersionGt)
+            | Some(OperatorType::VersionGte)
+            | Some(OperatorType::VersionLt)
+            | Some(OperatorType::VersionLte)
+            | Some(OperatorType::VersionEq)
+            | Some(OperatorType::VersionNeq) => Target::Version(
+                get_string(&target).and_then(|s| Version::parse(&s, self.strict_versions)),
+            ),
+            Some(OperatorType::Any) | Some(OperatorType::None) => {
+                Target::Set(target_strings(&target, true))
+            }
+            Some(OperatorType::AnyCaseSensitive) | Some(OperatorType::NoneCaseSensitive) => {
+                Target::Set(target_strings(&target, false))
+            }
+            Some(OperatorType::StrStartsWithAny)
+            | Some(OperatorType::StrEndsWithAny)
+            | Some(OperatorType::StrContainsAny)
+            | Some(OperatorType::StrContainsNone) => Target::Strings(target_strings(&target, true)),
+            Some(OperatorType::StrMatches) => {
+                Target::Regex(get_string(&target).and_then(|pattern| {
+                    self.regexes
+                        .entry(pattern)
+                        .or_insert_with_key(|pattern| compile_regex(pattern))
+                        .clone()
+                }))
+            }
+            Some(OperatorType::Before) | Some(OperatorType::After) | Some(OperatorType::On) => {
+                Target::Timestamp(get_unix_epoch_millis(&target))
+            }
+            _ => Target::Value(target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{Compiler, SpecMap, Target};
+    use crate::evaluator::models::{ConfigCondition, ConfigSpec};
+
+    fn gate(name: &str, target_gate: &str) -> ConfigSpec {
+        serde_json::from_value(json!({
+            "name": name,
+            "type": "feature_gate",
+            "salt": "salt",
+            "enabled": true,
+            "defaultValue": false,
+            "rules": [{
+                "name": "rule",
+                "id": "rule_id",
+                "salt": "",
+                "passPercentage": 100,
+                "idType": "userID",
+                "returnValue": true,
+                "conditions": [{
+                    "type": "pass_gate",
+                    "targetValue": target_gate,
+                    "idType": "userID",
+                }],
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compile_specs() {
+        let gates = vec![gate("a", "b"), gate("b", "missing"), gate("a", "a")];
+        let mut compiler = Compiler::new(false, gates.iter());
+        let specs = compiler.compile_specs(gates.clone());
+
+        // The last spec with a name wins but keeps the index of the first one
+        assert_eq!(specs.iter().count(), 2);
+        let a = specs.get("a").unwrap();
+        assert_eq!(a.rules[0].conditions[0].gate, Some(0));
+        assert_eq!(a.rules[0].bucket_salt, "salt.rule_id.");
+        let b = specs.get_index(1).unwrap();
+        assert_eq!(b.spec.name, "b");
+        assert_eq!(b.rules[0].conditions[0].gate, None);
+
+        // Names that aren't gates are looked up in the holdouts
+        let holdouts = [gate("holdout", "a"), gate("a", "a")];
+        let mut compiler = Compiler::new(false, gates.iter()).with_holdouts(holdouts.iter());
+        let mut condition = |target: &str| {
+            compiler.compile_condition(
+                gate("c", target)
+                    .rules
+                    .unwrap()
+                    .remove(0)
+                    .conditions
+                    .remove(0),
+            )
+        };
+        let a = condition("a");
+        assert_eq!((a.gate, a.holdout), (Some(0), None));
+        let holdout = condition("holdout");
+        assert_eq!((holdout.gate, holdout.holdout), (None, Some(0)));
+    }
+
+    #[test]
+    fn test_mark_cycles() {
+        let gates = vec![
+            gate("a", "b"),
+            gate("b", "c"),
+            gate("c", "a"),
+            gate("d", "a"),
+            gate("e", "e"),
+        ];
+        let mut compiler = Compiler::new(false, gates.iter());
+        let mut specs = compiler.compile_specs(gates);
+
+        assert_eq!(
+            specs.mark_cycles(),
+            vec![
+                vec!["a".to_string(), "b".into(), "c".into(), "a".into()],
+                vec!["e".to_string(), "e".into()],
+            ]
+        );
+        let cyclic: Vec<_> = specs
+            .iter()
+            .filter(|spec| spec.cyclic)
+            .map(|spec| spec.spec.name.as_str())
+            .collect();
+        assert_eq!(cyclic, vec!["a", "b", "c", "e"]);
+    }
+
+    fn spec(name: &str, mut condition: serde_json::Value, delegate: Option<&str>) -> ConfigSpec {
+        condition["idType"] = json!("userID");
+        serde_json::from_value(json!({
+            "name": name,
+            "type": "dynamic_config",
+            "salt": "salt",
+            "enabled": true,
+            "defaultValue": {},
+            "rules": [{
+                "name": "rule",
+                "id": "rule_id",
+                "salt": "",
+                "passPercentage": 100,
+                "idType": "userID",
+                "returnValue": {},
+                "configDelegate": delegate,
+                "conditions": [condition],
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mark_time_dependent() {
+        let now = json!({"type": "current_time", "operator": "after", "targetValue": 0});
+        let gates = vec![
+            gate("checks_checks_time", "checks_time"),
+            gate("checks_time", "time"),
+            spec("time", now.clone(), None),
+            gate("other", "missing"),
+            gate("checks_time_holdout", "time_holdout"),
+        ];
+        let holdouts = vec![
+            spec("time_holdout", now.clone(), None),
+            gate("holdout_checks_checks_time", "checks_time"),
+            gate("holdout", "other"),
+        ];
+        let mut compiler = Compiler::new(false, gates.iter()).with_holdouts(holdouts.iter());
+        let mut gates = compiler.compile_specs(gates);
+        let mut holdouts = compiler.compile_specs(holdouts);
+        SpecMap::mark_time_dependent_gates(&mut gates, &mut holdouts);
+        let mut experiments = compiler.compile_specs(vec![
+            spec("time_experiment", now.clone(), None),
+            spec(
+                "checks_time_experiment",
+                json!({"type": "pass_gate", "targetValue": "checks_checks_time"}),
+                None,
+            ),
+            spec("experiment", json!({"type": "public"}), None),
+            // Only layers delegate
+            spec(
+                "delegating_experiment",
+                json!({"type": "public"}),
+                Some("time_experiment"),
+            ),
+        ]);
+        experiments.mark_time_dependent(&gates, &holdouts, &SpecMap::default());
+        let mut layers = compiler.compile_layers(vec![
+            spec(
+                "time_layer",
+                json!({"type": "public"}),
+                Some("time_experiment"),
+            ),
+            spec("layer", json!({"type": "public"}), Some("experiment")),
+        ]);
+        layers.mark_time_dependent(&gates, &holdouts, &experiments);
+
+        let time_dependent = |specs: &SpecMap| -> Vec<String> {
+            specs
+                .iter()
+                .filter(|spec| spec.time_dependent)
+                .map(|spec| spec.spec.name.clone())
+                .collect()
+        };
+        assert_eq!(
+            time_dependent(&gates),
+            vec![
+                "checks_checks_time",
+                "checks_time",
+                "time",
+                "checks_time_holdout"
+            ]
+        );
+        assert_eq!(
+            time_dependent(&holdouts),
+            vec!["time_holdout", "holdout_checks_checks_time"]
+        );
+        assert_eq!(
+            time_dependent(&experiments),
+            vec!["time_experiment", "checks_time_experiment"]
+        );
+        assert_eq!(time_dependent(&layers), vec!["time_layer"]);
+    }
+
+    #[test]
+    fn test_compile_targets() {
+        let mut compiler = Compiler::new(true, std::iter::empty());
+        let mut compile = |operator: &str, target: serde_json::Value| {
+            let condition: ConfigCondition = serde_json::from_value(json!({
+                "type": "user_field",
+                "operator": operator,
+                "field": "email",
+                "targetValue": target,
+                "idType": "userID",
+            }))
+            .unwrap();
+            compiler.compile_condition(condition).target
+        };
+
+        match compile("any", json!(["A", 1, null])) {
+            Target::Set(set) => {
+                assert_eq!(set.len(), 2);
+                assert!(set.contains("a") && set.contains("1"));
+            }
+            _ => panic!("any should compile to a set"),
+        }
+        match compile("any_case_sensitive", json!(["A"])) {
+            Target::Set(set) => assert!(set.contains("A")),
+            _ => panic!("any_case_sensitive should compile to a set"),
+        }
+        assert!(matches!(compile("gt", json!("1.5")), Target::Number(Some(n)) if n == 1.5));
+        assert!(matches!(
+            compile("version_gt", json!("1.x")),
+            Target::Version(None)
+        ));
+        assert!(matches!(
+            compile("before", json!("1970-01-02")),
+            Target::Timestamp(Some(86_400_000))
+        ));
+        assert!(matches!(
+            compile("after", json!(-9_223_372_036_854_776_i64)),
+            Target::Timestamp(None)
+        ));
+        assert!(matches!(compile("eq", json!(3)), Target::Value(_)));
+    }
+}
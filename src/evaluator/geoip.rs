@@ -0,0 +1,158 @@
+use std::{net::IpAddr, path::Path};
+
+use crate::error::{Result, StatsigError};
+
+/// A contiguous range of addresses, stored as IPv6 (IPv4 is mapped into ::ffff:0:0/96).
+#[derive(Debug)]
+struct IpRange {
+    start: u128,
+    end: u128,
+    country: String,
+}
+
+/// Resolves IP addresses to ISO country codes from a local CSV database.
+///
+/// Each line is either `start_ip,end_ip,country` or `network/prefix,country`. Addresses
+/// can be written in dotted/colon notation or as integers, values may be quoted, and a
+/// header line is ignored.
+#[derive(Debug)]
+pub struct IpCountryLookup {
+    ranges: Vec<IpRange>,
+}
+
+fn to_u128(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u128::from(ip.to_ipv6_mapped()),
+        IpAddr::V6(ip) => u128::from(ip),
+    }
+}
+
+fn parse_address(s: &str) -> Option<u128> {
+    if let Ok(ip) = s.parse::<IpAddr>() {
+        return Some(to_u128(ip));
+    }
+    match s.parse::<u128>().ok()? {
+        n if n <= u32::MAX as u128 => Some(n | (0xffff << 32)),
+        n => Some(n),
+    }
+}
+
+fn parse_network(s: &str) -> Option<(u128, u128)> {
+    let (addr, prefix) = s.split_once('/')?;
+    let addr: IpAddr = addr.parse().ok()?;
+    let prefix: u32 = prefix.parse().ok()?;
+    let prefix = match addr {
+        IpAddr::V4(_) if prefix <= 32 => prefix + 96,
+        IpAddr::V6(_) if prefix <= 128 => prefix,
+        _ => return None,
+    };
+    let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+    let start = to_u128(addr) & mask;
+    Some((start, start | !mask))
+}
+
+fn parse_line(line: &str) -> Option<Option<IpRange>> {
+    let fields: Vec<&str> = line
+        .split(',')
+        .map(|f| f.trim().trim_matches('"'))
+        .collect();
+    let (start, end, country) = match fields.as_slice() {
+        [network, country] => {
+            let (start, end) = parse_network(network)?;
+            (start, end, *country)
+        }
+        [start, end, country, ..] => (parse_address(start)?, parse_address(end)?, *country),
+        _ => return None,
+    };
+    // Unassigned ranges are usually marked with "-"
+    if country.is_empty() || country == "-" || start > end {
+        return Some(None);
+    }
+    Some(Some(IpRange {
+        start,
+        end,
+        country: country.to_ascii_uppercase(),
+    }))
+}
+
+impl IpCountryLookup {
+    pub fn from_csv_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            StatsigError::InvalidOption(format!(
+                "failed to read geoip database {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Self::from_csv(&contents)
+    }
+
+    pub fn from_csv(contents: &str) -> Result<Self> {
+        let mut ranges = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match parse_line(line) {
+                Some(Some(range)) => ranges.push(range),
+                Some(None) => (),
+                // Tolerate a header line
+                None if i == 0 => (),
+                None => {
+                    return Err(StatsigError::InvalidOption(format!(
+                        "invalid geoip database line {}: {}",
+                        i + 1,
+                        line
+                    )))
+                }
+            }
+        }
+        ranges.sort_by_key(|r| r.start);
+        Ok(Self { ranges })
+    }
+
+    /// Returns the country code for the address, if it is valid and in a known range.
+    pub fn lookup(&self, ip: &str) -> Option<&str> {
+        let ip = to_u128(ip.trim().parse().ok()?);
+        let idx = self.ranges.partition_point(|r| r.start <= ip);
+        let range = self.ranges.get(idx.checked_sub(1)?)?;
+        if ip <= range.end {
+            Some(&range.country)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IpCountryLookup;
+
+    #[test]
+    fn test_lookup() {
+        let lookup = IpCountryLookup::from_csv(
+            "start,end,country
+            1.0.0.0,1.0.0.255,au
+            \"16777472\",\"16778239\",\"CN\"
+            2.16.0.0/13,FR
+            2001:db8::/32,NL
+            8.8.8.0,8.8.8.255,-",
+        )
+        .unwrap();
+
+        assert_eq!(lookup.lookup("1.0.0.1"), Some("AU"));
+        assert_eq!(lookup.lookup("1.0.1.10"), Some("CN"));
+        assert_eq!(lookup.lookup("2.23.255.255"), Some("FR"));
+        assert_eq!(lookup.lookup("2001:db8::1"), Some("NL"));
+        assert_eq!(lookup.lookup("8.8.8.8"), None);
+        assert_eq!(lookup.lookup("2.24.0.0"), None);
+        assert_eq!(lookup.lookup("0.0.0.1"), None);
+        assert_eq!(lookup.lookup("not an ip"), None);
+    }
+
+    #[test]
+    fn test_invalid_line() {
+        assert!(IpCountryLookup::from_csv("1.0.0.0,1.0.0.255,AU\nbroken").is_err());
+    }
+}
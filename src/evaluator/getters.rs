@@ -1,3 +1,4 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use sha2::{Digest, Sha256};
 
 use super::models::ConfigSpecType;
@@ -39,20 +40,52 @@ pub fn get_string(v: &serde_json::Value) -> Option<String> {
     }
 }
 
-pub fn get_unix_epoch(v: &serde_json::Value) -> i64 {
+pub fn get_bool(v: &serde_json::Value) -> Option<bool> {
+    match v {
+        serde_json::Value::Bool(b) => Some(*b),
+        serde_json::Value::String(s) => s.to_ascii_lowercase().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parses a timestamp string, either an RFC 3339 date time or a plain `YYYY-MM-DD` date.
+///
+/// Date times without an offset and plain dates are taken as UTC.
+fn parse_timestamp_millis(s: &str) -> Option<i64> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.timestamp_millis());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(dt.timestamp_millis());
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.timestamp_millis())
+}
+
+/// Returns the value as milliseconds since the unix epoch.
+///
+/// Numbers are taken as seconds unless they are too big to be, in which case they are
+/// already in milliseconds. Strings can also be RFC 3339 date times or plain dates.
+/// Anything else, like null or a string that isn't a date, returns `None`, as do seconds that
+/// overflow when converted to milliseconds.
+pub fn get_unix_epoch_millis(v: &serde_json::Value) -> Option<i64> {
     let val = match v {
-        serde_json::Value::Null => 0,
-        serde_json::Value::Bool(_) => 0,
-        serde_json::Value::Number(n) => n.as_i64().unwrap_or_default(),
-        serde_json::Value::String(s) => s.parse().unwrap_or_default(),
-        serde_json::Value::Array(_) => 0,
-        serde_json::Value::Object(_) => 0,
+        serde_json::Value::Number(n) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64))?,
+        serde_json::Value::String(s) => match s.trim().parse() {
+            Ok(val) => val,
+            Err(_) => return parse_timestamp_millis(s.trim()),
+        },
+        _ => return None,
     };
     if val > i32::MAX as i64 {
         // handle receiving value in milliseconds
-        val / 1000
+        Some(val)
     } else {
-        val
+        val.checked_mul(1000)
     }
 }
 
@@ -63,6 +96,7 @@ pub fn get_config_value(
     match config_type {
         ConfigSpecType::DynamicConfig => Some(v.clone()),
         ConfigSpecType::FeatureGate => None,
+        ConfigSpecType::Holdout => None,
         ConfigSpecType::Unknown => None,
     }
 }
@@ -0,0 +1,245 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::PoisonError,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossbeam::sync::ShardedLock;
+use sha2::{Digest, Sha256};
+
+use super::models::IdListMetadata;
+
+/// Length of the base64 encoded sha256 prefix used to identify a unit id in a list.
+const HASHED_ID_LEN: usize = 8;
+
+/// Hashes a unit id the same way the ids in the lists are hashed.
+pub fn hash_unit_id(id: &str) -> String {
+    let digest = Sha256::digest(id.as_bytes());
+    let mut encoded = STANDARD.encode(digest);
+    encoded.truncate(HASHED_ID_LEN);
+    encoded
+}
+
+/// Packs a hashed id into an integer, so each entry in a list takes 8 bytes.
+fn pack(hashed_id: &str) -> Option<u64> {
+    let bytes = hashed_id.as_bytes();
+    if bytes.is_empty() || bytes.len() > HASHED_ID_LEN {
+        return None;
+    }
+    let mut packed = [0u8; HASHED_ID_LEN];
+    packed[..bytes.len()].copy_from_slice(bytes);
+    Some(u64::from_le_bytes(packed))
+}
+
+/// A downloaded ID list, holding the hashed ids of its members.
+#[derive(Debug, Clone)]
+pub struct IdList {
+    pub metadata: IdListMetadata,
+    ids: HashSet<u64>,
+}
+
+impl IdList {
+    pub fn new(metadata: IdListMetadata) -> Self {
+        Self {
+            metadata,
+            ids: HashSet::new(),
+        }
+    }
+
+    /// Applies the `+id` and `-id` lines of a list file, ignoring anything else.
+    pub fn apply_changes(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let line = line.trim();
+            let (op, id) = match (line.get(..1), line.get(1..).and_then(pack)) {
+                (Some(op), Some(id)) => (op, id),
+                _ => continue,
+            };
+            match op {
+                "+" => {
+                    self.ids.insert(id);
+                }
+                "-" => {
+                    self.ids.remove(&id);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    pub fn contains_hashed(&self, hashed_id: &str) -> bool {
+        pack(hashed_id).is_some_and(|id| self.ids.contains(&id))
+    }
+}
+
+/// All the ID lists known by the evaluator, keyed by list name.
+pub struct IdLists {
+    lists: ShardedLock<HashMap<String, IdList>>,
+}
+
+impl IdLists {
+    pub fn new() -> Self {
+        Self {
+            lists: ShardedLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn metadata(&self, name: &str) -> Option<IdListMetadata> {
+        self.lists
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .map(|list| list.metadata.clone())
+    }
+
+    /// Applies a downloaded chunk that starts at byte `offset` of the list file.
+    ///
+    /// A chunk starting at zero replaces whatever was known about the list. Only whole lines
+    /// are applied, unless the chunk reaches the end of the file, and the stored metadata
+    /// keeps the number of bytes applied so far as its size, so the next chunk starts at the
+    /// beginning of a line.
+    pub fn apply_download(&self, metadata: &IdListMetadata, offset: u64, contents: &str) {
+        let contents = if offset + contents.len() as u64 >= metadata.size {
+            contents
+        } else {
+            contents.rfind('\n').map_or("", |end| &contents[..=end])
+        };
+        let mut lists = self.lists.write().unwrap_or_else(PoisonError::into_inner);
+        let list = lists
+            .entry(metadata.name.clone())
+            .or_insert_with(|| IdList::new(metadata.clone()));
+        if offset == 0 {
+            *list = IdList::new(metadata.clone());
+        }
+        list.apply_changes(contents);
+        list.metadata = IdListMetadata {
+            size: offset + contents.len() as u64,
+            ..metadata.clone()
+        };
+    }
+
+    /// Drops the lists that are no longer returned by the API.
+    pub fn retain(&self, names: &HashSet<&String>) {
+        self.lists
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|name, _| names.contains(name));
+    }
+
+    pub fn contains(&self, list_name: &str, unit_id: &str) -> bool {
+        let hashed_id = hash_unit_id(unit_id);
+        self.lists
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(list_name)
+            .is_some_and(|list| list.contains_hashed(&hashed_id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{hash_unit_id, IdList, IdLists};
+    use crate::evaluator::models::IdListMetadata;
+
+    #[test]
+    fn test_apply_changes() {
+        let lists = IdLists::new();
+        let mut list = IdList::new(IdListMetadata {
+            name: "list".to_string(),
+            size: 0,
+            url: None,
+            creation_time: 0,
+            file_id: None,
+        });
+        list.apply_changes(&format!(
+            "+{}\n+{}\n+{}\n-{}\ninvalid\n+waytoolongtobeanid\n",
+            hash_unit_id("1"),
+            hash_unit_id("2"),
+            hash_unit_id("3"),
+            hash_unit_id("2"),
+        ));
+        assert_eq!(list.ids.len(), 2);
+        lists
+            .lists
+            .write()
+            .unwrap()
+            .insert("list".to_string(), list);
+
+        assert!(lists.contains("list", "1"));
+        assert!(!lists.contains("list", "2"));
+        assert!(lists.contains("list", "3"));
+        assert!(!lists.contains("other_list", "1"));
+    }
+
+    #[test]
+    fn test_apply_download() {
+        let lists = IdLists::new();
+        let metadata = IdListMetadata {
+            name: "list".to_string(),
+            size: 100,
+            url: None,
+            creation_time: 1,
+            file_id: Some("file_1".to_string()),
+        };
+        let first = format!("+{}\n+{}\n", hash_unit_id("1"), hash_unit_id("2"));
+        lists.apply_download(&metadata, 0, &first);
+        assert_eq!(lists.metadata("list").unwrap().size, 20);
+
+        lists.apply_download(&metadata, 20, &format!("-{}\n", hash_unit_id("1")));
+        assert_eq!(lists.metadata("list").unwrap().size, 30);
+        assert!(!lists.contains("list", "1"));
+        assert!(lists.contains("list", "2"));
+
+        // A line cut off by the end of the chunk is left for the next one
+        let partial = format!("+{}\n+{}", hash_unit_id("4"), hash_unit_id("5"));
+        lists.apply_download(&metadata, 30, &partial[..15]);
+        assert_eq!(lists.metadata("list").unwrap().size, 40);
+        assert!(lists.contains("list", "4"));
+        assert!(!lists.contains("list", "5"));
+        lists.apply_download(&metadata, 40, &format!("+{}\n", hash_unit_id("5")));
+        assert_eq!(lists.metadata("list").unwrap().size, 50);
+        assert!(lists.contains("list", "5"));
+
+        // The last line of the file is applied even without a line break
+        let last = IdListMetadata {
+            size: 59,
+            ..metadata.clone()
+        };
+        lists.apply_download(&last, 50, &format!("+{}", hash_unit_id("6")));
+        assert_eq!(lists.metadata("list").unwrap().size, 59);
+        assert!(lists.contains("list", "6"));
+
+        // Starting over from zero drops the previous ids
+        lists.apply_download(&metadata, 0, &format!("+{}\n", hash_unit_id("3")));
+        assert_eq!(lists.metadata("list").unwrap().size, 10);
+        assert!(!lists.contains("list", "2"));
+        assert!(lists.contains("list", "3"));
+    }
+
+    #[test]
+    fn test_poisoned_lock() {
+        let lists = IdLists::new();
+        let _ = std::thread::scope(|s| {
+            s.spawn(|| {
+                let _guard = lists.lists.write().unwrap();
+                panic!("poison the lock");
+            })
+            .join()
+        });
+        assert!(lists.lists.is_poisoned());
+
+        // A panic elsewhere doesn't stop the lists from syncing and being checked
+        let metadata = IdListMetadata {
+            name: "list".to_string(),
+            size: 10,
+            url: None,
+            creation_time: 1,
+            file_id: None,
+        };
+        lists.apply_download(&metadata, 0, &format!("+{}\n", hash_unit_id("1")));
+        assert!(lists.contains("list", "1"));
+        lists.retain(&HashSet::new());
+        assert!(!lists.contains("list", "1"));
+    }
+}
@@ -1,226 +1,579 @@
 use std::{
-    cmp::max,
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+        Arc, PoisonError,
+    },
     time::{Duration, SystemTime},
 };
 
+use arc_swap::ArcSwap;
 use chrono::{Datelike, TimeZone, Utc};
 use crossbeam::sync::ShardedLock;
 use serde_json::json;
+use tracing::{event, Level};
 
 use crate::{
     evaluator::{getters::get_config_value, models::OperatorType},
-    models::StatsigUser,
+    models::{
+        ConditionExplanation, EvaluationReason, Explanation, ExplanationOutcome, RuleExplanation,
+        StatsigUser,
+    },
 };
 
-use self::getters::{get_hash, get_numeric_value, get_string, get_unix_epoch};
+use self::compiled::{CompiledCondition, CompiledRule, CompiledSpec, Compiler, SpecMap, Target};
+use self::geoip::IpCountryLookup;
+use self::getters::{get_bool, get_hash, get_numeric_value, get_string, get_unix_epoch_millis};
+use self::id_lists::IdLists;
 use self::models::{
-    ConditionType, ConfigCondition, ConfigData, ConfigRule, ConfigSpec, EvalResult,
+    AllEvalResults, ConditionType, ConfigData, ConfigRule, ConfigSpec, ConfigSpecType, EntityType,
+    EvalResult,
 };
+use self::user_agent::UserAgent;
+use self::version::Version;
 
+mod client_init;
+mod compiled;
+pub mod geoip;
 mod getters;
+pub mod id_lists;
 pub mod models;
+mod user_agent;
+mod version;
 
 fn compare_numbers(
-    v1: &serde_json::Value,
-    v2: &serde_json::Value,
+    value: &serde_json::Value,
+    target: Option<f64>,
     f: fn(f64, f64) -> bool,
 ) -> bool {
-    let n1 = get_numeric_value(v1);
-    let n2 = get_numeric_value(v2);
-    if n1.is_none() || n2.is_none() {
-        false
-    } else {
-        f(n1.unwrap_or_default(), n2.unwrap_or_default())
+    match (get_numeric_value(value), target) {
+        (Some(n1), Some(n2)) => f(n1, n2),
+        _ => false,
     }
 }
 
-fn compare_versions(v1: &serde_json::Value, v2: &serde_json::Value, f: fn(i32) -> bool) -> bool {
-    let n1 = get_string(v1);
-    let n2 = get_string(v2);
-    if n1.is_none() || n2.is_none() {
-        return false;
+/// Compares two versions by semver precedence.
+///
+/// In strict mode versions that can't be parsed never match.
+fn compare_versions(
+    value: &serde_json::Value,
+    target: Option<&Version>,
+    strict: bool,
+    f: fn(Ordering) -> bool,
+) -> bool {
+    let value = get_string(value).and_then(|s| Version::parse(&s, strict));
+    match (value, target) {
+        (Some(v1), Some(v2)) => f(v1.cmp(v2)),
+        _ => false,
     }
+}
 
-    let mut n1 = n1.unwrap();
-    let mut n2 = n2.unwrap();
-    if let Some(index) = n1.find('-') {
-        n1 = n1[0..index].to_string();
-    }
-    if let Some(index) = n2.find('-') {
-        n2 = n2[0..index].to_string();
+/// Case insensitive match of the value against any of the lowercased target strings.
+fn match_string_in_array(
+    value: &serde_json::Value,
+    targets: &[String],
+    f: fn(&str, &str) -> bool,
+) -> bool {
+    match get_string(value) {
+        None => false,
+        Some(v) => {
+            let value = v.to_ascii_lowercase();
+            targets.iter().any(|t| f(&value, t))
+        }
     }
+}
 
-    let n1_parts: Vec<&str> = n1.split('.').collect();
-    let n2_parts: Vec<&str> = n2.split('.').collect();
-    let mut cnt = 0;
-    let mut ret = 0;
-    while ret == 0 && cnt < max(n1_parts.len(), n2_parts.len()) {
-        let n1_val = if cnt < n1_parts.len() {
-            n1_parts[cnt].parse().unwrap_or_default()
-        } else {
-            0
-        };
-        let n2_val = if cnt < n2_parts.len() {
-            n2_parts[cnt].parse().unwrap_or_default()
-        } else {
-            0
-        };
-        if n1_val < n2_val {
-            ret = -1;
-        }
-        if n2_val < n1_val {
-            ret = 1;
-        }
-        cnt += 1;
+fn is_empty_value(value: &serde_json::Value) -> bool {
+    value.is_null() || value.as_str() == Some("")
+}
+
+/// Equality that takes the type of both sides into account.
+///
+/// Numbers and booleans compare by value even when one side is a string, anything else
+/// falls back to an exact string comparison.
+fn values_equal(value: &serde_json::Value, target: &serde_json::Value) -> bool {
+    if target.is_null() || is_empty_value(value) {
+        return is_empty_value(value) && is_empty_value(target);
+    }
+    if value.is_boolean() || target.is_boolean() {
+        return matches!((get_bool(value), get_bool(target)), (Some(v), Some(t)) if v == t);
     }
+    if value.is_number() || target.is_number() {
+        return matches!(
+            (get_numeric_value(value), get_numeric_value(target)),
+            (Some(v), Some(t)) if v == t
+        );
+    }
+    match (get_string(value), get_string(target)) {
+        (Some(v), Some(t)) => v == t,
+        _ => value == target,
+    }
+}
 
-    f(ret)
+/// Checks if the value, or any element of an array value, is in the target set.
+///
+/// The set is already lowercased when the match is case insensitive.
+fn match_any_in_array(
+    value: &serde_json::Value,
+    targets: &HashSet<String>,
+    case_sensitive: bool,
+) -> bool {
+    let normalize = |v: &serde_json::Value| {
+        get_string(v).map(|s| {
+            if case_sensitive {
+                s
+            } else {
+                s.to_ascii_lowercase()
+            }
+        })
+    };
+    match value.as_array() {
+        Some(arr) => arr
+            .iter()
+            .filter_map(normalize)
+            .any(|v| targets.contains(&v)),
+        None => normalize(value).is_some_and(|v| targets.contains(&v)),
+    }
 }
 
-fn eval_pass_percent(user: &StatsigUser, rule: &ConfigRule, spec: &ConfigSpec) -> bool {
-    let rule_salt = if rule.salt.is_empty() {
-        &rule.id
+/// Checks the elements of an array value against a target array.
+///
+/// With `all` every target element must be in the value, otherwise any one is enough.
+/// Non-array values never match.
+fn array_contains(
+    value: &serde_json::Value,
+    target: &serde_json::Value,
+    all: bool,
+) -> Option<bool> {
+    let values: Vec<String> = value.as_array()?.iter().filter_map(get_string).collect();
+    let mut targets = target.as_array()?.iter().map(get_string);
+    Some(if all {
+        targets.all(|t| t.is_some_and(|t| values.contains(&t)))
     } else {
-        &rule.salt
-    };
+        targets.any(|t| t.is_some_and(|t| values.contains(&t)))
+    })
+}
+
+/// The UTC day of a timestamp in milliseconds.
+fn day(millis: i64) -> Option<(i32, u32, u32)> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .map(|d| (d.year(), d.month(), d.day()))
+}
+
+/// Maximum number of distinct user agents kept parsed, the cache is reset when it fills up.
+const MAX_USER_AGENT_CACHE_SIZE: usize = 1000;
+
+/// The user's bucket for the rule's pass percentage, in 0..10000.
+fn pass_percent_bucket(user: &StatsigUser, rule: &CompiledRule) -> u64 {
     let hash = get_hash(format!(
-        "{}.{}.{}",
-        spec.salt,
-        rule_salt,
-        user.get_unit_id(&rule.id_type)
+        "{}{}",
+        rule.bucket_salt,
+        user.get_unit_id(&rule.rule.id_type)
     ));
-    ((hash % 10000) as f64) < ((rule.pass_percentage) * 100.0)
+    hash % 10000
+}
+
+fn eval_pass_percent(bucket: u64, rule: &ConfigRule) -> bool {
+    (bucket as f64) < ((rule.pass_percentage) * 100.0)
+}
+
+/// Holdouts can come with the holdouts or with the gates, and only one of the type or the
+/// entity may say so.
+fn is_holdout(spec: &ConfigSpec) -> bool {
+    matches!(spec.r#type, ConfigSpecType::Holdout) || spec.entity == Some(EntityType::Holdout)
+}
+
+/// Holdouts and segments are only checked through the specs that use them, they aren't
+/// handed out on their own.
+fn is_holdout_or_segment(spec: &ConfigSpec) -> bool {
+    is_holdout(spec) || spec.entity == Some(EntityType::SegmentList)
+}
+
+/// The snake_case name of an operator or condition type, as it comes in the specs.
+fn spec_name<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Maximum number of gates checked through nested pass_gate/fail_gate conditions, a layer
+/// delegating to an experiment counts as one more.
+const MAX_GATE_DEPTH: usize = 32;
+
+/// State shared by everything evaluated for one top-level check.
+struct EvalContext<'a> {
+    specs: &'a ConfigSnapshot,
+    /// Number of pass_gate/fail_gate conditions and layer delegations being evaluated.
+    depth: usize,
+    /// Values of the gates checked so far by pass_gate/fail_gate conditions, by name.
+    gates: HashMap<String, bool>,
+}
+
+impl<'a> EvalContext<'a> {
+    fn new(specs: &'a ConfigSnapshot) -> Self {
+        Self {
+            specs,
+            depth: 0,
+            gates: HashMap::new(),
+        }
+    }
+}
+
+/// The specs of one sync, replaced as a whole on every refresh.
+#[derive(Default)]
+struct ConfigSnapshot {
+    dynamic_configs: SpecMap,
+    gates: SpecMap,
+    /// Only checked through pass_gate/fail_gate conditions, never looked up by name.
+    holdouts: SpecMap,
+    layer_configs: SpecMap,
+    /// Milliseconds since the unix epoch of the sync, 0 before the first one.
+    sync_time: u64,
 }
 
 pub struct Evaluator {
-    dynamic_configs: ShardedLock<HashMap<String, ConfigSpec>>,
-    gates: ShardedLock<HashMap<String, ConfigSpec>>,
-    #[allow(unused)]
-    layer_configs: ShardedLock<HashMap<String, ConfigSpec>>,
+    /// Compiled specs, see `compiled`. Evaluations load the snapshot once and use it
+    /// throughout, so they never mix specs from different syncs.
+    specs: ArcSwap<ConfigSnapshot>,
+    ip_country_lookup: Option<IpCountryLookup>,
+    user_agents: ShardedLock<HashMap<String, Arc<UserAgent>>>,
+    id_lists: IdLists,
+    /// Whether unparseable versions fail `version_*` conditions instead of counting as 0.
+    strict_versions: bool,
+    /// Milliseconds since the unix epoch when the first configs were loaded, 0 before that.
+    init_time: AtomicU64,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Evaluator {
     pub fn new() -> Self {
         Self {
-            dynamic_configs: ShardedLock::new(HashMap::new()),
-            gates: ShardedLock::new(HashMap::new()),
-            layer_configs: ShardedLock::new(HashMap::new()),
+            specs: ArcSwap::from_pointee(ConfigSnapshot::default()),
+            ip_country_lookup: None,
+            user_agents: ShardedLock::new(HashMap::new()),
+            id_lists: IdLists::new(),
+            strict_versions: false,
+            init_time: AtomicU64::new(0),
         }
     }
 
+    /// Resolves `ip_based` country conditions locally with the given database.
+    pub fn with_ip_country_lookup(mut self, lookup: IpCountryLookup) -> Self {
+        self.ip_country_lookup = Some(lookup);
+        self
+    }
+
+    /// Makes `version_*` conditions fail when either version can't be parsed.
+    pub fn with_strict_versions(mut self, strict: bool) -> Self {
+        self.strict_versions = strict;
+        self
+    }
+
     pub fn refresh_configs(&self, data: ConfigData) {
-        let new_dynamic_configs = data
-            .dynamic_configs
-            .unwrap_or_default()
-            .into_iter()
-            .map(|d| (d.name.clone(), d))
-            .collect();
-        let feature_gates = data
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_else(|_| Duration::from_secs(0))
+            .as_millis() as u64;
+        // Holdouts are referenced by the rules of the specs they apply to through
+        // pass_gate/fail_gate conditions, but they aren't gates that can be checked on their own.
+        let (holdouts, feature_gates): (Vec<_>, Vec<_>) = data
             .feature_gates
             .unwrap_or_default()
             .into_iter()
-            .map(|f| (f.name.clone(), f))
-            .collect();
-        let layer_configs = data
-            .layer_configs
-            .unwrap_or_default()
-            .into_iter()
-            .map(|f| (f.name.clone(), f))
-            .collect();
+            .chain(data.holdouts.unwrap_or_default())
+            .partition(is_holdout);
+        let mut compiler = Compiler::new(self.strict_versions, feature_gates.iter())
+            .with_holdouts(holdouts.iter());
+        let mut gates = compiler.compile_specs(feature_gates);
+        let mut holdouts = compiler.compile_specs(holdouts);
+        for cycle in gates.mark_cycles() {
+            event!(
+                Level::WARN,
+                "Gates check each other in a cycle, they will fail with an error: {}",
+                cycle.join(" -> ")
+            );
+        }
+        SpecMap::mark_time_dependent_gates(&mut gates, &mut holdouts);
+        let mut dynamic_configs = compiler.compile_specs(data.dynamic_configs.unwrap_or_default());
+        dynamic_configs.mark_time_dependent(&gates, &holdouts, &SpecMap::default());
+        let mut layer_configs = compiler.compile_layers(data.layer_configs.unwrap_or_default());
+        layer_configs.mark_time_dependent(&gates, &holdouts, &dynamic_configs);
+        let snapshot = ConfigSnapshot {
+            gates,
+            holdouts,
+            dynamic_configs,
+            layer_configs,
+            sync_time: data.time.unwrap_or(now),
+        };
+        self.specs.store(Arc::new(snapshot));
 
-        let mut dynamic_configs = self
-            .dynamic_configs
-            .write()
-            .expect("should not be poisoned");
-        *dynamic_configs = new_dynamic_configs;
-        let mut gates = self.gates.write().expect("should not be poisoned");
-        *gates = feature_gates;
-        let mut layers = self.layer_configs.write().expect("should not be poisoned");
-        *layers = layer_configs;
+        let _ = self.init_time.compare_exchange(
+            0,
+            now,
+            AtomicOrdering::Relaxed,
+            AtomicOrdering::Relaxed,
+        );
     }
 
-    pub fn check_gate_internal(&self, user: &StatsigUser, gate_name: &String) -> EvalResult {
-        match self
-            .gates
-            .read()
-            .expect("should always be able to acquire read lock")
-            .get(gate_name)
-        {
-            Some(gate) => self.eval_spec(user, gate),
-            None => EvalResult::fail(),
+    pub fn config_sync_time(&self) -> u64 {
+        self.specs.load().sync_time
+    }
+
+    pub fn init_time(&self) -> u64 {
+        self.init_time.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Result for a name that isn't in the specs.
+    fn unrecognized(&self) -> EvalResult {
+        EvalResult {
+            reason: if self.init_time() == 0 {
+                EvaluationReason::Uninitialized
+            } else {
+                EvaluationReason::Unrecognized
+            },
+            ..EvalResult::fail()
         }
     }
 
-    pub fn get_dynamic_config_internal(
+    pub fn id_lists(&self) -> &IdLists {
+        &self.id_lists
+    }
+
+    pub fn check_gate_internal(&self, user: &StatsigUser, gate_name: &str) -> EvalResult {
+        self.eval_named(user, gate_name, |specs| &specs.gates)
+    }
+
+    pub fn get_dynamic_config_internal(&self, user: &StatsigUser, config_name: &str) -> EvalResult {
+        self.eval_named(user, config_name, |specs| &specs.dynamic_configs)
+    }
+
+    pub fn get_layer_internal(&self, user: &StatsigUser, layer_name: &str) -> EvalResult {
+        self.eval_named(user, layer_name, |specs| &specs.layer_configs)
+    }
+
+    /// Evaluates the spec named `name` from the map `specs_of` picks in the current snapshot.
+    fn eval_named(
         &self,
         user: &StatsigUser,
-        config_name: &String,
+        name: &str,
+        specs_of: fn(&ConfigSnapshot) -> &SpecMap,
     ) -> EvalResult {
-        match self
-            .dynamic_configs
-            .read()
-            .expect("should always be able to acquire read lock")
-            .get(config_name)
-        {
-            Some(spec) => self.eval_spec(user, spec),
-            None => EvalResult::fail(),
+        let specs = self.specs.load();
+        let (res, time_dependent) = match specs_of(&specs).get(name) {
+            Some(spec) => (self.eval_spec(&specs, user, spec), spec.time_dependent),
+            None => (self.unrecognized(), false),
+        };
+        EvalResult {
+            config_sync_time: specs.sync_time,
+            time_dependent,
+            ..res
+        }
+    }
+
+    /// Evaluates every gate, config and layer for the user against the same specs, holdouts and
+    /// segments are left out.
+    pub fn get_all_evaluations_internal(&self, user: &StatsigUser) -> AllEvalResults {
+        let specs = self.specs.load_full();
+        let eval_all = |map: &SpecMap| {
+            map.iter()
+                .filter(|spec| !is_holdout_or_segment(&spec.spec))
+                .map(|spec| (spec.spec.name.clone(), self.eval_spec(&specs, user, spec)))
+                .collect()
+        };
+        AllEvalResults {
+            gates: eval_all(&specs.gates),
+            dynamic_configs: eval_all(&specs.dynamic_configs),
+            layer_configs: eval_all(&specs.layer_configs),
+        }
+    }
+
+    pub fn explain_gate_internal(&self, user: &StatsigUser, gate_name: &str) -> Explanation {
+        let specs = self.specs.load();
+        self.explain_spec(&specs, user, gate_name, specs.gates.get(gate_name))
+    }
+
+    pub fn explain_dynamic_config_internal(
+        &self,
+        user: &StatsigUser,
+        config_name: &str,
+    ) -> Explanation {
+        let specs = self.specs.load();
+        self.explain_spec(
+            &specs,
+            user,
+            config_name,
+            specs.dynamic_configs.get(config_name),
+        )
+    }
+
+    fn explain_spec(
+        &self,
+        specs: &ConfigSnapshot,
+        user: &StatsigUser,
+        name: &str,
+        spec: Option<&CompiledSpec>,
+    ) -> Explanation {
+        let mut explanation = Explanation::new(name, ExplanationOutcome::Unrecognized);
+        if let Some(spec) = spec {
+            let res = self.eval_spec_traced(
+                &mut EvalContext::new(specs),
+                user,
+                spec,
+                Some(&mut explanation),
+            );
+            explanation.pass = res.pass;
+            explanation.value = res.config_value;
+            explanation.rule_id = res.rule_id;
+            explanation.group_name = res.group_name;
         }
+        explanation
     }
 
-    fn eval_spec(&self, user: &StatsigUser, spec: &ConfigSpec) -> EvalResult {
+    fn eval_spec(
+        &self,
+        specs: &ConfigSnapshot,
+        user: &StatsigUser,
+        spec: &CompiledSpec,
+    ) -> EvalResult {
+        self.eval_spec_traced(&mut EvalContext::new(specs), user, spec, None)
+    }
+
+    /// Evaluates the spec, recording every step in `trace` when given.
+    fn eval_spec_traced(
+        &self,
+        ctx: &mut EvalContext<'_>,
+        user: &StatsigUser,
+        compiled: &CompiledSpec,
+        mut trace: Option<&mut Explanation>,
+    ) -> EvalResult {
+        let spec = &compiled.spec;
         if !spec.enabled {
+            if let Some(trace) = trace {
+                trace.outcome = ExplanationOutcome::Disabled;
+            }
             return EvalResult {
                 id: "disabled".to_string(),
+                reason: EvaluationReason::Disabled,
                 ..EvalResult::fail()
             };
         }
+        if compiled.cyclic {
+            if let Some(trace) = trace {
+                trace.outcome = ExplanationOutcome::Error;
+            }
+            return EvalResult {
+                config_value: get_config_value(&spec.default_value, spec.r#type),
+                ..EvalResult::error()
+            };
+        }
 
         let mut exposures: Vec<HashMap<String, String>> = vec![];
-        if let Some(rules) = &spec.rules {
-            for rule in rules.iter() {
-                let res = self.eval_rule(user, rule);
+        for compiled_rule in compiled.rules.iter() {
+            let rule = &compiled_rule.rule;
+            let mut rule_trace = trace.as_ref().map(|_| RuleExplanation {
+                rule_id: rule.id.clone(),
+                name: rule.name.clone(),
+                pass: false,
+                conditions: vec![],
+                bucket: None,
+                pass_percentage: rule.pass_percentage,
+                delegate: None,
+            });
+            let res = self.eval_rule(
+                ctx,
+                user,
+                compiled_rule,
+                rule_trace.as_mut().map(|t| &mut t.conditions),
+            );
+            if let (Some(trace), Some(mut rule_trace)) = (trace.as_deref_mut(), rule_trace) {
+                rule_trace.pass = res.pass;
+                trace.rules.push(rule_trace);
                 if res.fetch_from_server {
-                    return res;
+                    trace.outcome = ExplanationOutcome::FetchFromServer;
+                } else if res.reason == EvaluationReason::Error {
+                    trace.outcome = ExplanationOutcome::Error;
                 }
-                res.secondary_exposures
-                    .into_iter()
-                    .for_each(|e| exposures.push(e));
-
-                if res.pass {
-                    // TODO: Eval delegates
-                    let pass = eval_pass_percent(user, rule, spec);
-                    let (config_value, group, group_name, rule_id) = if pass {
-                        (
-                            get_config_value(&rule.return_value, spec.r#type),
-                            rule.name.clone(),
-                            rule.group_name.clone(),
-                            rule.id.clone(),
-                        )
-                    } else {
-                        (
-                            get_config_value(&spec.default_value, spec.r#type),
-                            "default".to_owned(),
-                            Some("default".to_owned()),
-                            "default".to_owned(),
-                        )
-                    };
+            }
+            if res.fetch_from_server {
+                return res;
+            }
+            if res.reason == EvaluationReason::Error {
+                return EvalResult {
+                    config_value: get_config_value(&spec.default_value, spec.r#type),
+                    ..EvalResult::error()
+                };
+            }
+            res.secondary_exposures
+                .into_iter()
+                .for_each(|e| exposures.push(e));
 
-                    return EvalResult {
-                        pass,
-                        id: rule.id.clone(),
-                        secondary_exposures: exposures,
-                        config_value,
-                        group,
-                        group_name,
-                        rule_id,
-                        ..Default::default()
+            if res.pass {
+                let delegate_trace = trace.as_deref_mut().and_then(|t| t.rules.last_mut());
+                if let Some(delegated) = compiled
+                    .delegates
+                    .then(|| self.eval_delegate(ctx, user, rule, &exposures, delegate_trace))
+                    .flatten()
+                {
+                    if let Some(trace) = trace {
+                        trace.outcome = ExplanationOutcome::Delegated;
+                    }
+                    return delegated;
+                }
+
+                let bucket = pass_percent_bucket(user, compiled_rule);
+                let pass = eval_pass_percent(bucket, rule);
+                if let Some(trace) = trace {
+                    if let Some(rule_trace) = trace.rules.last_mut() {
+                        rule_trace.bucket = Some(bucket);
+                    }
+                    trace.outcome = if pass {
+                        ExplanationOutcome::RuleMatched
+                    } else {
+                        ExplanationOutcome::RolloutFailed
                     };
                 }
+                let (config_value, group, group_name, rule_id) = if pass {
+                    (
+                        get_config_value(&rule.return_value, spec.r#type),
+                        rule.name.clone(),
+                        rule.group_name.clone(),
+                        rule.id.clone(),
+                    )
+                } else {
+                    (
+                        get_config_value(&spec.default_value, spec.r#type),
+                        "default".to_owned(),
+                        Some("default".to_owned()),
+                        "default".to_owned(),
+                    )
+                };
+
+                return EvalResult {
+                    pass,
+                    id: rule.id.clone(),
+                    is_experiment_group: pass && rule.is_experiment_group.unwrap_or(false),
+                    secondary_exposures: exposures,
+                    config_value,
+                    group,
+                    group_name,
+                    rule_id,
+                    ..Default::default()
+                };
             }
         }
 
         // No rules matched, return the default value
+        if let Some(trace) = trace {
+            trace.outcome = ExplanationOutcome::NoRuleMatched;
+        }
         EvalResult {
             secondary_exposures: exposures,
             config_value: get_config_value(&spec.default_value, spec.r#type),
@@ -228,19 +581,126 @@ impl Evaluator {
         }
     }
 
-    fn eval_rule(&self, user: &StatsigUser, rule: &ConfigRule) -> EvalResult {
+    fn in_id_list(&self, value: &serde_json::Value, condition: &CompiledCondition) -> bool {
+        match (
+            get_string(value),
+            condition
+                .condition
+                .target_value
+                .as_ref()
+                .and_then(|t| t.as_str()),
+        ) {
+            (Some(id), Some(list_name)) => self.id_lists.contains(list_name, &id),
+            _ => false,
+        }
+    }
+
+    fn parse_user_agent(&self, ua: &str) -> Arc<UserAgent> {
+        if let Some(parsed) = self
+            .user_agents
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(ua)
+        {
+            return parsed.clone();
+        }
+
+        let parsed = Arc::new(UserAgent::parse(ua));
+        let mut user_agents = self
+            .user_agents
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if user_agents.len() >= MAX_USER_AGENT_CACHE_SIZE {
+            user_agents.clear();
+        }
+        user_agents.insert(ua.to_string(), parsed.clone());
+        parsed
+    }
+
+    /// Evaluates the experiment a layer rule hands allocation to, if it exists.
+    fn eval_delegate(
+        &self,
+        ctx: &mut EvalContext<'_>,
+        user: &StatsigUser,
+        rule: &ConfigRule,
+        exposures: &[HashMap<String, String>],
+        trace: Option<&mut RuleExplanation>,
+    ) -> Option<EvalResult> {
+        let delegate_name = rule.config_delegate.as_ref()?;
+        let specs = ctx.specs;
+        let delegate = specs.dynamic_configs.get(delegate_name)?;
+
+        let mut delegate_trace = trace
+            .as_ref()
+            .map(|_| Explanation::new(delegate_name, ExplanationOutcome::Unrecognized));
+        // Delegating counts toward the depth like checking a gate does
+        let mut res = if ctx.depth >= MAX_GATE_DEPTH {
+            EvalResult::error()
+        } else {
+            ctx.depth += 1;
+            let res = self.eval_spec_traced(ctx, user, delegate, delegate_trace.as_mut());
+            ctx.depth -= 1;
+            res
+        };
+        if let (Some(trace), Some(mut delegate_trace)) = (trace, delegate_trace) {
+            delegate_trace.pass = res.pass;
+            delegate_trace.value = res.config_value.clone();
+            delegate_trace.rule_id = res.rule_id.clone();
+            delegate_trace.group_name = res.group_name.clone();
+            trace.delegate = Some(Box::new(delegate_trace));
+        }
+        if res.fetch_from_server {
+            return Some(res);
+        }
+        let mut secondary_exposures = exposures.to_vec();
+        secondary_exposures.append(&mut res.secondary_exposures);
+        Some(EvalResult {
+            secondary_exposures,
+            undelegated_secondary_exposures: Some(exposures.to_vec()),
+            config_delegate: Some(delegate_name.clone()),
+            explicit_parameters: delegate
+                .spec
+                .explicit_parameters
+                .as_ref()
+                .map(|params| params.iter().map(|p| (p.clone(), true)).collect()),
+            ..res
+        })
+    }
+
+    fn eval_rule(
+        &self,
+        ctx: &mut EvalContext<'_>,
+        user: &StatsigUser,
+        rule: &CompiledRule,
+        mut trace: Option<&mut Vec<ConditionExplanation>>,
+    ) -> EvalResult {
         let mut result = EvalResult {
             pass: true,
             ..Default::default()
         };
-        for condition in rule.conditions.iter() {
-            let res = self.eval_condition(user, condition);
+        for compiled in rule.conditions.iter() {
+            let (res, user_value) = self.eval_condition(ctx, user, compiled);
+            if let Some(trace) = trace.as_deref_mut() {
+                let condition = &compiled.condition;
+                trace.push(ConditionExplanation {
+                    condition_type: spec_name(&condition.r#type),
+                    operator: condition.operator.as_ref().map(spec_name),
+                    field: condition.field.clone(),
+                    user_value,
+                    target_value: condition.target_value.clone(),
+                    pass: res.pass,
+                    fetch_from_server: res.fetch_from_server,
+                });
+            }
             if !res.pass {
                 result.pass = false;
             }
             if res.fetch_from_server {
                 result.fetch_from_server = true;
             }
+            if res.reason == EvaluationReason::Error {
+                result.reason = EvaluationReason::Error;
+            }
             res.secondary_exposures
                 .into_iter()
                 .for_each(|e| result.secondary_exposures.push(e));
@@ -248,20 +708,61 @@ impl Evaluator {
         result
     }
 
-    fn eval_condition(&self, user: &StatsigUser, condition: &ConfigCondition) -> EvalResult {
+    /// Evaluates the condition, also returning the value it resolved from the user.
+    fn eval_condition(
+        &self,
+        ctx: &mut EvalContext<'_>,
+        user: &StatsigUser,
+        compiled: &CompiledCondition,
+    ) -> (EvalResult, serde_json::Value) {
+        let condition = &compiled.condition;
         let empty = json!(null);
         let empty_str: String = "".to_string();
         let value = match condition.r#type {
-            ConditionType::Public => return EvalResult::pass(),
+            ConditionType::Public => return (EvalResult::pass(), empty),
             ConditionType::FailGate | ConditionType::PassGate => {
                 let gate_name = match condition.target_value.as_ref().unwrap_or(&empty).as_str() {
-                    None => return EvalResult::fail(),
+                    None => return (EvalResult::fail(), empty),
                     Some(s) => s,
                 };
-                let mut res = self.check_gate_internal(user, &gate_name.to_string());
+                // The exposures of a gate checked before in this evaluation are already
+                // recorded, so they stay unique and in the order they were first seen
+                if let Some(&gate_pass) = ctx.gates.get(gate_name) {
+                    let pass = (condition.r#type == ConditionType::PassGate && gate_pass)
+                        || (condition.r#type == ConditionType::FailGate && !gate_pass);
+                    return (
+                        EvalResult {
+                            pass,
+                            ..Default::default()
+                        },
+                        json!(gate_pass),
+                    );
+                }
+
+                let specs = ctx.specs;
+                let gate = match (compiled.gate, compiled.holdout) {
+                    (Some(index), _) => specs.gates.get_index(index),
+                    (None, Some(index)) => specs.holdouts.get_index(index),
+                    (None, None) => None,
+                };
+                let mut res = match gate {
+                    // Too deep to be a sane config, give up before running out of stack
+                    Some(_) if ctx.depth >= MAX_GATE_DEPTH => EvalResult::error(),
+                    Some(gate) => {
+                        ctx.depth += 1;
+                        let res = self.eval_spec_traced(ctx, user, gate, None);
+                        ctx.depth -= 1;
+                        res
+                    }
+                    None => self.unrecognized(),
+                };
                 if res.fetch_from_server {
-                    return EvalResult::fetch_from_server();
+                    return (EvalResult::fetch_from_server(), empty);
+                }
+                if res.reason == EvaluationReason::Error {
+                    return (EvalResult::error(), empty);
                 }
+                ctx.gates.insert(gate_name.to_string(), res.pass);
                 let new_exposure = HashMap::from([
                     ("gate".to_string(), gate_name.to_string()),
                     ("gateValue".to_string(), res.pass.to_string()),
@@ -271,20 +772,43 @@ impl Evaluator {
                 exposures.push(new_exposure);
                 let pass = (condition.r#type == ConditionType::PassGate && res.pass)
                     || (condition.r#type == ConditionType::FailGate && !res.pass);
-                return EvalResult {
-                    pass,
-                    secondary_exposures: exposures,
-                    ..Default::default()
-                };
+                return (
+                    EvalResult {
+                        pass,
+                        secondary_exposures: exposures,
+                        ..Default::default()
+                    },
+                    json!(res.pass),
+                );
             }
             ConditionType::IpBased => {
-                return EvalResult::fetch_from_server(); // TODO
+                let field = condition.field.as_ref().unwrap_or(&empty_str);
+                let from_user = user.get_field_value(field);
+                if !is_empty_value(&from_user) || !field.eq_ignore_ascii_case("country") {
+                    from_user
+                } else {
+                    match &self.ip_country_lookup {
+                        None => return (EvalResult::fetch_from_server(), empty),
+                        Some(lookup) => {
+                            json!(user.ip.as_ref().and_then(|ip| lookup.lookup(ip)))
+                        }
+                    }
+                }
             }
             ConditionType::UaBased => {
-                return EvalResult::fetch_from_server(); // TODO
+                let field = condition.field.as_ref().unwrap_or(&empty_str);
+                let from_user = user.get_field_value(field);
+                if !is_empty_value(&from_user) {
+                    from_user
+                } else {
+                    match &user.user_agent {
+                        None => json!(null),
+                        Some(ua) => json!(self.parse_user_agent(ua).get_field(field)),
+                    }
+                }
             }
             ConditionType::UserField => {
-                json!(user.get_field(condition.field.as_ref().unwrap_or(&empty_str)))
+                user.get_field_value(condition.field.as_ref().unwrap_or(&empty_str))
             }
             ConditionType::EnvironmentField => {
                 json!(user
@@ -294,178 +818,125 @@ impl Evaluator {
             ConditionType::CurrentTime => json!(SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap_or_else(|_| Duration::from_secs(0))
-                .as_secs()),
-            ConditionType::UserBucket => {
-                if let Some(salt) = condition
-                    .additional_values
-                    .as_ref()
-                    .and_then(|h| h.get(&"salt".to_string()))
-                {
-                    json!(
-                        get_hash(format!("{}.{}", salt, user.get_unit_id(&condition.id_type)))
-                            % 1000
-                    )
-                } else {
-                    json!(null)
-                }
-            }
+                .as_millis() as u64),
+            ConditionType::UserBucket => match &compiled.bucket_salt {
+                Some(salt) => json!(
+                    get_hash(format!("{}{}", salt, user.get_unit_id(&condition.id_type))) % 1000
+                ),
+                None => json!(null),
+            },
             ConditionType::UnitId => json!(user.get_unit_id(&condition.id_type)),
             ConditionType::Unknown => {
-                return EvalResult::fetch_from_server();
+                return (EvalResult::fetch_from_server(), empty);
             }
         };
 
-        let pass = match condition
+        let strict = self.strict_versions;
+        let operator = condition
             .operator
             .as_ref()
-            .unwrap_or(&OperatorType::Unknown)
-        {
-            OperatorType::Gt => compare_numbers(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |n1, n2| n1 > n2,
-            ),
-            OperatorType::Gte => compare_numbers(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |n1, n2| n1 >= n2,
-            ),
-            OperatorType::Lt => compare_numbers(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |n1, n2| n1 < n2,
-            ),
-            OperatorType::Lte => compare_numbers(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |n1, n2| n1 <= n2,
-            ),
-            OperatorType::VersionGt => compare_versions(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |cmp| cmp > 0,
-            ),
-            OperatorType::VersionGte => compare_versions(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |cmp| cmp >= 0,
-            ),
-            OperatorType::VersionLt => compare_versions(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |cmp| cmp < 0,
-            ),
-            OperatorType::VersionLte => compare_versions(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |cmp| cmp <= 0,
-            ),
-            OperatorType::VersionEq => compare_versions(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |cmp| cmp == 0,
-            ),
-            OperatorType::VersionNeq => compare_versions(
-                &value,
-                condition.target_value.as_ref().unwrap_or(&empty),
-                |cmp| cmp != 0,
-            ),
-            // Case insensitive
-            OperatorType::Any => {
-                let lower_val = get_string(&value).map(|v| v.to_ascii_lowercase());
-                match condition.target_value.as_ref().unwrap_or(&empty).as_array() {
-                    None => false,
-                    Some(arr) => arr
-                        .iter()
-                        .any(|v| get_string(v).map(|val| val.to_ascii_lowercase()) == lower_val),
-                }
+            .unwrap_or(&OperatorType::Unknown);
+        let pass = match (operator, &compiled.target) {
+            (OperatorType::Gt, Target::Number(t)) => compare_numbers(&value, *t, |n1, n2| n1 > n2),
+            (OperatorType::Gte, Target::Number(t)) => {
+                compare_numbers(&value, *t, |n1, n2| n1 >= n2)
             }
-            // Case insensitive
-            OperatorType::None => {
-                let lower_val = get_string(&value).map(|v| v.to_ascii_lowercase());
-                match condition.target_value.as_ref().unwrap_or(&empty).as_array() {
-                    None => true,
-                    Some(arr) => !arr
-                        .iter()
-                        .any(|v| get_string(v).map(|val| val.to_ascii_lowercase()) == lower_val),
-                }
+            (OperatorType::Lt, Target::Number(t)) => compare_numbers(&value, *t, |n1, n2| n1 < n2),
+            (OperatorType::Lte, Target::Number(t)) => {
+                compare_numbers(&value, *t, |n1, n2| n1 <= n2)
             }
-            OperatorType::AnyCaseSensitive => {
-                match condition.target_value.as_ref().unwrap_or(&empty).as_array() {
-                    None => false,
-                    Some(arr) => arr.iter().any(|v| match get_string(v) {
-                        None => false,
-                        Some(s) => s == value,
-                    }),
-                }
+            (OperatorType::VersionGt, Target::Version(t)) => {
+                compare_versions(&value, t.as_ref(), strict, |cmp| cmp.is_gt())
             }
-            OperatorType::NoneCaseSensitive => {
-                match condition.target_value.as_ref().unwrap_or(&empty).as_array() {
-                    None => true,
-                    Some(arr) => !arr.iter().any(|v| match get_string(v) {
-                        None => false,
-                        Some(s) => s == value,
-                    }),
-                }
+            (OperatorType::VersionGte, Target::Version(t)) => {
+                compare_versions(&value, t.as_ref(), strict, |cmp| cmp.is_ge())
             }
-            OperatorType::StrStartsWithAny => {
-                return EvalResult::fetch_from_server(); // TODO
+            (OperatorType::VersionLt, Target::Version(t)) => {
+                compare_versions(&value, t.as_ref(), strict, |cmp| cmp.is_lt())
             }
-            OperatorType::StrEndsWithAny => {
-                return EvalResult::fetch_from_server(); // TODO
+            (OperatorType::VersionLte, Target::Version(t)) => {
+                compare_versions(&value, t.as_ref(), strict, |cmp| cmp.is_le())
             }
-            OperatorType::StrContainsAny => {
-                return EvalResult::fetch_from_server(); // TODO
+            (OperatorType::VersionEq, Target::Version(t)) => {
+                compare_versions(&value, t.as_ref(), strict, |cmp| cmp.is_eq())
             }
-            OperatorType::StrContainsNone => {
-                return EvalResult::fetch_from_server(); // TODO
+            (OperatorType::VersionNeq, Target::Version(t)) => {
+                compare_versions(&value, t.as_ref(), strict, |cmp| cmp.is_ne())
             }
-            OperatorType::StrMatches => {
-                return EvalResult::fetch_from_server(); // TODO
+            // Case insensitive
+            (OperatorType::Any, Target::Set(t)) => match_any_in_array(&value, t, false),
+            // Case insensitive
+            (OperatorType::None, Target::Set(t)) => !match_any_in_array(&value, t, false),
+            (OperatorType::AnyCaseSensitive, Target::Set(t)) => match_any_in_array(&value, t, true),
+            (OperatorType::NoneCaseSensitive, Target::Set(t)) => {
+                !match_any_in_array(&value, t, true)
             }
-            OperatorType::Eq => match condition.target_value.as_ref().unwrap_or(&empty).as_str() {
-                None => {
-                    (value.is_string() && matches!(&value.as_str(), Some(""))) || value.is_null()
-                }
-                Some(target) => target == value,
+            // Case insensitive
+            (OperatorType::StrStartsWithAny, Target::Strings(t)) => {
+                match_string_in_array(&value, t, |v, t| v.starts_with(t))
+            }
+            // Case insensitive
+            (OperatorType::StrEndsWithAny, Target::Strings(t)) => {
+                match_string_in_array(&value, t, |v, t| v.ends_with(t))
+            }
+            // Case insensitive
+            (OperatorType::StrContainsAny, Target::Strings(t)) => {
+                match_string_in_array(&value, t, |v, t| v.contains(t))
+            }
+            // Case insensitive
+            (OperatorType::StrContainsNone, Target::Strings(t)) => {
+                !match_string_in_array(&value, t, |v, t| v.contains(t))
+            }
+            (OperatorType::StrMatches, Target::Regex(re)) => match (get_string(&value), re) {
+                (Some(value), Some(re)) => re.is_match(&value),
+                _ => false,
             },
-            OperatorType::Neq => match condition.target_value.as_ref().unwrap_or(&empty).as_str() {
-                None => {
-                    !((value.is_string() && matches!(&value.as_str(), Some(""))) || value.is_null())
+            (OperatorType::Eq, Target::Value(t)) => values_equal(&value, t),
+            (OperatorType::Neq, Target::Value(t)) => !values_equal(&value, t),
+            (OperatorType::Before, Target::Timestamp(t)) => {
+                match (get_unix_epoch_millis(&value), t) {
+                    (Some(value), Some(t)) => value < *t,
+                    _ => false,
+                }
+            }
+            (OperatorType::After, Target::Timestamp(t)) => {
+                match (get_unix_epoch_millis(&value), t) {
+                    (Some(value), Some(t)) => value > *t,
+                    _ => false,
                 }
-                Some(target) => target != value,
-            },
-            OperatorType::Before => {
-                get_unix_epoch(&value)
-                    < get_unix_epoch(condition.target_value.as_ref().unwrap_or(&empty))
             }
-            OperatorType::After => {
-                get_unix_epoch(&value)
-                    > get_unix_epoch(condition.target_value.as_ref().unwrap_or(&empty))
+            (OperatorType::On, Target::Timestamp(t)) => {
+                let d1 = get_unix_epoch_millis(&value).and_then(day);
+                d1.is_some() && d1 == t.and_then(day)
             }
-            OperatorType::On => {
-                let d1 = Utc.timestamp(get_unix_epoch(&value), 0);
-                let d2 = Utc.timestamp(
-                    get_unix_epoch(condition.target_value.as_ref().unwrap_or(&empty)),
-                    0,
-                );
-                d1.day() == d2.day() && d1.month() == d2.month() && d1.year() == d2.year()
+            (OperatorType::InSegmentList, _) => self.in_id_list(&value, compiled),
+            (OperatorType::NotInSegmentList, _) => !self.in_id_list(&value, compiled),
+            (OperatorType::ArrayContainsAny, Target::Value(t)) => {
+                array_contains(&value, t, false).unwrap_or(false)
             }
-            OperatorType::InSegmentList => {
-                return EvalResult::fetch_from_server(); // TODO
+            (OperatorType::ArrayContainsNone, Target::Value(t)) => {
+                array_contains(&value, t, false).is_some_and(|found| !found)
             }
-            OperatorType::NotInSegmentList => {
-                return EvalResult::fetch_from_server(); // TODO
+            (OperatorType::ArrayContainsAll, Target::Value(t)) => {
+                array_contains(&value, t, true).unwrap_or(false)
             }
-            OperatorType::Unknown => {
-                return EvalResult::fetch_from_server();
+            (OperatorType::NotArrayContainsAll, Target::Value(t)) => {
+                array_contains(&value, t, true).is_some_and(|found| !found)
             }
+            (OperatorType::Unknown, _) => {
+                return (EvalResult::fetch_from_server(), value);
+            }
+            // The compiler always prepares the target for the operator
+            _ => false,
         };
 
-        EvalResult {
-            pass,
-            ..Default::default()
-        }
+        (
+            EvalResult {
+                pass,
+                ..Default::default()
+            },
+            value,
+        )
     }
 }
 
@@ -480,13 +951,36 @@ mod test {
     use serde_json::json;
 
     use super::{
+        compiled::{CompiledCondition, Compiler, Target},
         models::{
             ConditionType, ConfigCondition, ConfigRule, ConfigSpec, ConfigSpecType, EvalResult,
             OperatorType,
         },
-        Evaluator,
+        EvalContext, Evaluator,
+    };
+    use crate::{
+        evaluator::{geoip::IpCountryLookup, models::ConfigData},
+        models::{EvaluationReason, ExplanationOutcome, StatsigUser},
     };
-    use crate::{evaluator::models::ConfigData, models::StatsigUser};
+
+    /// Compiles a condition against the gates the evaluator has.
+    fn compile(evaluator: &Evaluator, condition: &ConfigCondition) -> CompiledCondition {
+        let specs = evaluator.specs.load();
+        Compiler::new(
+            evaluator.strict_versions,
+            specs.gates.iter().map(|gate| &gate.spec),
+        )
+        .compile_condition(condition.clone())
+    }
+
+    fn eval_compiled(
+        evaluator: &Evaluator,
+        user: &StatsigUser,
+        condition: &CompiledCondition,
+    ) -> (EvalResult, serde_json::Value) {
+        let specs = evaluator.specs.load();
+        evaluator.eval_condition(&mut EvalContext::new(&specs), user, condition)
+    }
 
     fn test_eval_condition(
         name: &str,
@@ -497,6 +991,7 @@ mod test {
         let default_configs = ConfigData {
             dynamic_configs: None,
             layer_configs: None,
+            holdouts: None,
             has_updates: true,
             time: None,
             feature_gates: Some(vec![
@@ -507,6 +1002,9 @@ mod test {
                     enabled: true,
                     default_value: json!(null),
                     id_type: None,
+                    explicit_parameters: None,
+                    entity: None,
+                    is_active: None,
                     rules: Some(vec![ConfigRule {
                         name: "user_id_match".to_string(),
                         group_name: Some("User id match".to_owned()),
@@ -515,6 +1013,8 @@ mod test {
                         pass_percentage: 100.0,
                         id_type: "userID".to_string(),
                         return_value: json!(null),
+                        config_delegate: None,
+                        is_experiment_group: None,
                         conditions: vec![ConfigCondition {
                             r#type: ConditionType::UnitId,
                             operator: Some(OperatorType::Eq),
@@ -532,6 +1032,9 @@ mod test {
                     enabled: true,
                     default_value: json!(null),
                     id_type: None,
+                    explicit_parameters: None,
+                    entity: None,
+                    is_active: None,
                     rules: Some(vec![ConfigRule {
                         name: "user_id_not_match".to_string(),
                         group_name: Some("User id not match".to_owned()),
@@ -540,6 +1043,8 @@ mod test {
                         pass_percentage: 100.0,
                         id_type: "userID".to_string(),
                         return_value: json!(null),
+                        config_delegate: None,
+                        is_experiment_group: None,
                         conditions: vec![ConfigCondition {
                             r#type: ConditionType::UnitId,
                             operator: Some(OperatorType::Neq),
@@ -554,7 +1059,7 @@ mod test {
         };
         let evaluator = Evaluator::new();
         evaluator.refresh_configs(default_configs);
-        let result = evaluator.eval_condition(user, condition);
+        let (result, _) = eval_compiled(&evaluator, user, &compile(&evaluator, condition));
         if result.pass != expected.pass || result.fetch_from_server != expected.fetch_from_server {
             Err(format!("{}: failed", name))
         } else {
@@ -570,11 +1075,21 @@ mod test {
                 ("not_userid".to_string(), "not_userid".to_string()),
                 ("ALL_CAPS".to_string(), "ALL_CAPS".to_string()),
             ])),
-            custom: Some(HashMap::from([(
-                "totalDeposit".to_string(),
-                "30".to_string(),
-            )])),
+            custom: Some(HashMap::from([
+                ("totalDeposit".to_string(), "30".to_string()),
+                ("signupDate".to_string(), "2024-03-01T10:00:00Z".to_string()),
+                ("malformedDate".to_string(), "next tuesday".to_string()),
+            ])),
+            custom_values: Some(HashMap::from([
+                ("level".to_string(), json!(7)),
+                ("verified".to_string(), json!(true)),
+                ("entitlements".to_string(), json!(["pro", "beta"])),
+                // Overflows when converted from seconds to milliseconds
+                ("overflowingDate".to_string(), json!(-9_223_372_036_854_776_i64)),
+            ])),
             app_version: Some("300.10.2".to_string()),
+            email: Some("abc@email.com".to_string()),
+            user_agent: Some("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/118.0.5993.88 Safari/537.36".to_string()),
             ..StatsigUser::new(user_id, "production".to_string())
         };
         [
@@ -912,6 +1427,32 @@ mod test {
                 },
                 EvalResult::fail(),
             ),
+            (
+                "version_gt_pre_release_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::VersionGt),
+                    field: Some("appVersion".to_string()),
+                    target_value: Some(json!("300.10.2-beta.1".to_string())),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "version_eq_build_metadata_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::VersionEq),
+                    field: Some("appVersion".to_string()),
+                    target_value: Some(json!("300.10.2+1234".to_string())),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
             (
                 "any_pass",
                 &user,
@@ -984,10 +1525,244 @@ mod test {
                 EvalResult::fail(),
             ),
             (
-                "eq_pass",
+                "str_starts_with_any_pass",
                 &user,
                 &ConfigCondition {
-                    r#type: ConditionType::UnitId,
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrStartsWithAny),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!(["nope", "ABC@"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "str_starts_with_any_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrStartsWithAny),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!(["email.com"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "str_starts_with_any_number",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrStartsWithAny),
+                    field: Some("totalDeposit".to_string()),
+                    target_value: Some(json!([3])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "str_ends_with_any_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrEndsWithAny),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!(["@Email.com"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "str_ends_with_any_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrEndsWithAny),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!(["@other.com"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "str_contains_any_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrContainsAny),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!(["other", "C@EM"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "str_contains_any_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrContainsAny),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!(["other"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "str_contains_none_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrContainsNone),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!(["other"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "str_contains_none_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrContainsNone),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!(["other", "EMAIL"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "str_matches_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrMatches),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!("^[a-z]+@email\\.com$")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "str_matches_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrMatches),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!("^[0-9]+@")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "str_matches_invalid_pattern",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrMatches),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!("(unclosed")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "str_matches_too_big_pattern",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::StrMatches),
+                    field: Some("email".to_string()),
+                    target_value: Some(json!("\\w{1000}{1000}")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "ua_browser_name_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UaBased,
+                    operator: Some(OperatorType::Any),
+                    field: Some("browser_name".to_string()),
+                    target_value: Some(json!(["chrome", "firefox"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "ua_browser_name_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UaBased,
+                    operator: Some(OperatorType::None),
+                    field: Some("browser_name".to_string()),
+                    target_value: Some(json!(["Chrome"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "ua_browser_version_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UaBased,
+                    operator: Some(OperatorType::VersionGte),
+                    field: Some("browser_version".to_string()),
+                    target_value: Some(json!("118.0")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "ua_os_name_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UaBased,
+                    operator: Some(OperatorType::Any),
+                    field: Some("os_name".to_string()),
+                    target_value: Some(json!(["Windows"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "ua_os_version_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UaBased,
+                    operator: Some(OperatorType::VersionLt),
+                    field: Some("os_version".to_string()),
+                    target_value: Some(json!("10")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "eq_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UnitId,
                     operator: Some(OperatorType::Eq),
                     field: Some("field".to_string()),
                     target_value: Some(json!("user_id".to_string())),
@@ -1022,6 +1797,292 @@ mod test {
                 },
                 EvalResult::fail(),
             ),
+            (
+                "eq_pass_number",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Eq),
+                    field: Some("level".to_string()),
+                    target_value: Some(json!(7.0)),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "eq_pass_number_string",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Eq),
+                    field: Some("totalDeposit".to_string()),
+                    target_value: Some(json!(30)),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "eq_fail_number",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Eq),
+                    field: Some("level".to_string()),
+                    target_value: Some(json!("8")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "eq_pass_bool",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Eq),
+                    field: Some("verified".to_string()),
+                    target_value: Some(json!(true)),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "eq_pass_bool_string",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Eq),
+                    field: Some("verified".to_string()),
+                    target_value: Some(json!("TRUE")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "eq_fail_bool",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Eq),
+                    field: Some("verified".to_string()),
+                    target_value: Some(json!(false)),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "neq_pass_number",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Neq),
+                    field: Some("level".to_string()),
+                    target_value: Some(json!(8)),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "any_pass_array",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Any),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["enterprise", "BETA"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "any_fail_array",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Any),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["enterprise"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "any_case_sensitive_fail_array",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::AnyCaseSensitive),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["BETA"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "none_pass_array",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::None),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["enterprise"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "none_fail_array",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::None),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["PRO"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "array_contains_any_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::ArrayContainsAny),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["enterprise", "beta"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "array_contains_any_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::ArrayContainsAny),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["enterprise", "BETA"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "array_contains_any_fail_not_array",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::ArrayContainsAny),
+                    field: Some("totalDeposit".to_string()),
+                    target_value: Some(json!(["30"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "array_contains_none_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::ArrayContainsNone),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["enterprise"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "array_contains_none_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::ArrayContainsNone),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["enterprise", "pro"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "array_contains_none_fail_missing",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::ArrayContainsNone),
+                    field: Some("missing".to_string()),
+                    target_value: Some(json!(["enterprise"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "array_contains_all_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::ArrayContainsAll),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["beta", "pro"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "array_contains_all_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::ArrayContainsAll),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["beta", "enterprise"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "not_array_contains_all_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::NotArrayContainsAll),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["beta", "enterprise"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "not_array_contains_all_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::NotArrayContainsAll),
+                    field: Some("entitlements".to_string()),
+                    target_value: Some(json!(["pro"])),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
             (
                 "neq_pass",
                 &user,
@@ -1082,103 +2143,207 @@ mod test {
                 EvalResult::pass(),
             ),
             (
-                "before_fail",
+                "before_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::CurrentTime,
+                    operator: Some(OperatorType::Before),
+                    field: Some("field".to_string()),
+                    target_value: Some(json!(
+                        SystemTime::now()
+                            .sub(Duration::from_secs(300))
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs()
+                            * 1000
+                    )),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "after_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::CurrentTime,
+                    operator: Some(OperatorType::After),
+                    field: Some("field".to_string()),
+                    target_value: Some(json!(
+                        SystemTime::now()
+                            .sub(Duration::from_secs(300))
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs()
+                            * 1000
+                    )),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "after_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::CurrentTime,
+                    operator: Some(OperatorType::After),
+                    field: Some("field".to_string()),
+                    target_value: Some(json!(
+                        SystemTime::now()
+                            .add(Duration::from_secs(300))
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs()
+                            * 1000
+                    )),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "on_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::CurrentTime,
+                    operator: Some(OperatorType::On),
+                    field: Some("field".to_string()),
+                    target_value: Some(json!(
+                        SystemTime::now()
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs()
+                            * 1000
+                    )),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "on_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::CurrentTime,
+                    operator: Some(OperatorType::On),
+                    field: Some("field".to_string()),
+                    target_value: Some(json!(
+                        SystemTime::now()
+                            .add(Duration::from_secs(36 * 60 * 60))
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs()
+                            * 1000
+                    )),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "after_rfc3339_pass",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::After),
+                    field: Some("signupDate".to_string()),
+                    target_value: Some(json!("2024-03-01T09:59:59.999+00:00")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::pass(),
+            ),
+            (
+                "before_rfc3339_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Before),
+                    field: Some("signupDate".to_string()),
+                    target_value: Some(json!(1709287200000_i64)),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "before_overflowing_user_value_fail",
+                &user,
+                &ConfigCondition {
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Before),
+                    field: Some("overflowingDate".to_string()),
+                    target_value: Some(json!("2024-03-01")),
+                    id_type: "userid".to_string(),
+                    additional_values: None,
+                },
+                EvalResult::fail(),
+            ),
+            (
+                "after_overflowing_target_fail",
                 &user,
                 &ConfigCondition {
-                    r#type: ConditionType::CurrentTime,
-                    operator: Some(OperatorType::Before),
-                    field: Some("field".to_string()),
-                    target_value: Some(json!(
-                        SystemTime::now()
-                            .sub(Duration::from_secs(300))
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs()
-                            * 1000
-                    )),
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::After),
+                    field: Some("signupDate".to_string()),
+                    target_value: Some(json!(-9_223_372_036_854_776_i64)),
                     id_type: "userid".to_string(),
                     additional_values: None,
                 },
                 EvalResult::fail(),
             ),
             (
-                "after_pass",
+                "before_malformed_user_value_fail",
                 &user,
                 &ConfigCondition {
-                    r#type: ConditionType::CurrentTime,
-                    operator: Some(OperatorType::After),
-                    field: Some("field".to_string()),
-                    target_value: Some(json!(
-                        SystemTime::now()
-                            .sub(Duration::from_secs(300))
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs()
-                            * 1000
-                    )),
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Before),
+                    field: Some("malformedDate".to_string()),
+                    target_value: Some(json!("2024-03-01")),
                     id_type: "userid".to_string(),
                     additional_values: None,
                 },
-                EvalResult::pass(),
+                EvalResult::fail(),
             ),
             (
-                "after_fail",
+                "before_missing_user_value_fail",
                 &user,
                 &ConfigCondition {
-                    r#type: ConditionType::CurrentTime,
-                    operator: Some(OperatorType::After),
-                    field: Some("field".to_string()),
-                    target_value: Some(json!(
-                        SystemTime::now()
-                            .add(Duration::from_secs(300))
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs()
-                            * 1000
-                    )),
+                    r#type: ConditionType::UserField,
+                    operator: Some(OperatorType::Before),
+                    field: Some("missingDate".to_string()),
+                    target_value: Some(json!("2024-03-01")),
                     id_type: "userid".to_string(),
                     additional_values: None,
                 },
                 EvalResult::fail(),
             ),
             (
-                "on_pass",
+                "on_malformed_user_value_fail",
                 &user,
                 &ConfigCondition {
-                    r#type: ConditionType::CurrentTime,
+                    r#type: ConditionType::UserField,
                     operator: Some(OperatorType::On),
-                    field: Some("field".to_string()),
-                    target_value: Some(json!(
-                        SystemTime::now()
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs()
-                            * 1000
-                    )),
+                    field: Some("malformedDate".to_string()),
+                    target_value: Some(json!("2024-03-01")),
                     id_type: "userid".to_string(),
                     additional_values: None,
                 },
-                EvalResult::pass(),
+                EvalResult::fail(),
             ),
             (
-                "on_fail",
+                "on_date_pass",
                 &user,
                 &ConfigCondition {
-                    r#type: ConditionType::CurrentTime,
+                    r#type: ConditionType::UserField,
                     operator: Some(OperatorType::On),
-                    field: Some("field".to_string()),
-                    target_value: Some(json!(
-                        SystemTime::now()
-                            .add(Duration::from_secs(36 * 60 * 60))
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs()
-                            * 1000
-                    )),
+                    field: Some("signupDate".to_string()),
+                    target_value: Some(json!("2024-03-01")),
                     id_type: "userid".to_string(),
                     additional_values: None,
                 },
-                EvalResult::fail(),
+                EvalResult::pass(),
             ),
             (
                 "pass_gate_pass",
@@ -1296,19 +2461,12 @@ mod test {
             feature_gates: Some(vec![gate]),
             dynamic_configs: None,
             layer_configs: None,
+            holdouts: None,
             has_updates: false,
             time: None,
         });
-        assert!(
-            evaluator
-                .check_gate_internal(user, &"test_gate".to_string())
-                .pass
-        );
-        assert!(
-            !evaluator
-                .check_gate_internal(user, &"test_gate2".to_string())
-                .pass
-        );
+        assert!(evaluator.check_gate_internal(user, "test_gate").pass);
+        assert!(!evaluator.check_gate_internal(user, "test_gate2").pass);
 
         let gate: ConfigSpec = serde_json::from_value(json!({
             "name": "test_gate",
@@ -1338,13 +2496,669 @@ mod test {
             feature_gates: Some(vec![gate]),
             dynamic_configs: None,
             layer_configs: None,
+            holdouts: None,
             has_updates: false,
             time: None,
         });
+        assert!(!evaluator.check_gate_internal(user, "test_gate").pass);
+    }
+
+    #[test]
+    fn test_layer_delegates_to_experiment() {
+        let gate: ConfigSpec = serde_json::from_value(json!({
+            "name": "layer_gate",
+            "type": "feature_gate",
+            "salt": "salt",
+            "enabled": true,
+            "defaultValue": false,
+            "idType": "userID",
+            "rules": [{
+                "name": "public",
+                "id": "gate_rule",
+                "salt": "gate_rule_salt",
+                "passPercentage": 100,
+                "idType": "userID",
+                "returnValue": true,
+                "conditions": [{"type": "public", "idType": "userID"}],
+            }],
+        }))
+        .unwrap();
+        let experiment: ConfigSpec = serde_json::from_value(json!({
+            "name": "layer_experiment",
+            "type": "dynamic_config",
+            "salt": "exp_salt",
+            "enabled": true,
+            "defaultValue": {"color": "blue"},
+            "idType": "userID",
+            "explicitParameters": ["color"],
+            "rules": [{
+                "name": "test_group",
+                "groupName": "Test",
+                "id": "exp_rule",
+                "salt": "exp_rule_salt",
+                "passPercentage": 100,
+                "idType": "userID",
+                "returnValue": {"color": "green"},
+                "conditions": [{"type": "public", "idType": "userID"}],
+            }],
+        }))
+        .unwrap();
+        let layer: ConfigSpec = serde_json::from_value(json!({
+            "name": "layer",
+            "type": "dynamic_config",
+            "salt": "layer_salt",
+            "enabled": true,
+            "defaultValue": {"color": "blue"},
+            "idType": "userID",
+            "rules": [{
+                "name": "allocation",
+                "id": "layer_rule",
+                "salt": "layer_rule_salt",
+                "passPercentage": 100,
+                "idType": "userID",
+                "returnValue": {"color": "blue"},
+                "configDelegate": "layer_experiment",
+                "conditions": [{
+                    "type": "pass_gate",
+                    "targetValue": "layer_gate",
+                    "idType": "userID",
+                }],
+            }],
+        }))
+        .unwrap();
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(ConfigData {
+            feature_gates: Some(vec![gate]),
+            dynamic_configs: Some(vec![experiment]),
+            layer_configs: Some(vec![layer]),
+            holdouts: None,
+            has_updates: true,
+            time: None,
+        });
+
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        let res = evaluator.get_layer_internal(&user, "layer");
+        assert!(res.pass);
+        assert_eq!(res.config_value, Some(json!({"color": "green"})));
+        assert_eq!(res.rule_id, "exp_rule");
+        assert_eq!(res.group, "test_group");
+        assert_eq!(res.group_name, Some("Test".to_string()));
+        assert_eq!(res.config_delegate, Some("layer_experiment".to_string()));
+        assert_eq!(
+            res.explicit_parameters,
+            Some(HashMap::from([("color".to_string(), true)]))
+        );
+        let gate_exposure = HashMap::from([
+            ("gate".to_string(), "layer_gate".to_string()),
+            ("gateValue".to_string(), "true".to_string()),
+            ("ruleID".to_string(), "gate_rule".to_string()),
+        ]);
+        assert_eq!(res.secondary_exposures, vec![gate_exposure.clone()]);
+        assert_eq!(
+            res.undelegated_secondary_exposures,
+            Some(vec![gate_exposure])
+        );
+    }
+
+    #[test]
+    fn test_only_layers_delegate() {
+        let config = |name: &str, delegate: &str| {
+            json!({
+                "name": name,
+                "type": "dynamic_config",
+                "salt": "salt",
+                "enabled": true,
+                "defaultValue": {"from": "default"},
+                "idType": "userID",
+                "rules": [{
+                    "name": "rule",
+                    "id": format!("{}_rule", name),
+                    "salt": "rule_salt",
+                    "passPercentage": 100,
+                    "idType": "userID",
+                    "returnValue": {"from": name},
+                    "configDelegate": delegate,
+                    "conditions": [{"type": "public", "idType": "userID"}],
+                }],
+            })
+        };
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(
+            serde_json::from_value(json!({
+                "dynamic_configs": [
+                    config("self_delegating", "self_delegating"),
+                    config("ping", "pong"),
+                    config("pong", "ping"),
+                ],
+                "layer_configs": [config("layer", "ping")],
+                "has_updates": true,
+                "time": 1,
+            }))
+            .unwrap(),
+        );
+
+        // Configs keep their own value instead of following the delegate around
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        for name in ["self_delegating", "ping", "pong"] {
+            let res = evaluator.get_dynamic_config_internal(&user, name);
+            assert!(res.pass, "{}", name);
+            assert_eq!(res.config_value, Some(json!({"from": name})));
+            assert_eq!(res.config_delegate, None, "{}", name);
+        }
+
+        // The layer delegates once, ping doesn't go on to pong
+        let res = evaluator.get_layer_internal(&user, "layer");
+        assert_eq!(res.config_value, Some(json!({"from": "ping"})));
+        assert_eq!(res.config_delegate, Some("ping".to_string()));
+    }
+
+    #[test]
+    fn test_str_matches_compiled_on_refresh() {
+        let gate: ConfigSpec = serde_json::from_value(json!({
+            "name": "regex_gate",
+            "type": "feature_gate",
+            "salt": "salt",
+            "enabled": true,
+            "defaultValue": false,
+            "idType": "userID",
+            "rules": [{
+                "name": "regex",
+                "id": "regex_rule",
+                "salt": "regex_salt",
+                "passPercentage": 100,
+                "idType": "userID",
+                "returnValue": true,
+                "conditions": [
+                    {
+                        "type": "user_field",
+                        "operator": "str_matches",
+                        "field": "email",
+                        "targetValue": "@statsig\\.com$",
+                        "idType": "userID",
+                    },
+                    {
+                        "type": "user_field",
+                        "operator": "str_matches",
+                        "field": "email",
+                        "targetValue": "[invalid",
+                        "idType": "userID",
+                    },
+                ],
+            }],
+        }))
+        .unwrap();
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(ConfigData {
+            feature_gates: Some(vec![gate]),
+            dynamic_configs: None,
+            layer_configs: None,
+            holdouts: None,
+            has_updates: true,
+            time: None,
+        });
+        {
+            let specs = evaluator.specs.load();
+            let conditions = &specs.gates.get("regex_gate").unwrap().rules[0].conditions;
+            assert!(matches!(conditions[0].target, Target::Regex(Some(_))));
+            assert!(matches!(conditions[1].target, Target::Regex(None)));
+        }
+
+        let user = StatsigUser {
+            email: Some("someone@statsig.com".to_string()),
+            ..StatsigUser::new("user_id".to_string(), "production".to_string())
+        };
+        // The invalid pattern never matches, so the rule fails without fetching from the server
+        let res = evaluator.check_gate_internal(&user, "regex_gate");
+        assert!(!res.pass);
+        assert!(!res.fetch_from_server);
+    }
+
+    #[test]
+    fn test_ip_based_country() {
+        let condition = ConfigCondition {
+            r#type: ConditionType::IpBased,
+            operator: Some(OperatorType::Any),
+            field: Some("country".to_string()),
+            target_value: Some(json!(["BR", "PT"])),
+            id_type: "userid".to_string(),
+            additional_values: None,
+        };
+        let brazilian = StatsigUser {
+            ip: Some("177.0.0.1".to_string()),
+            ..StatsigUser::new("user_id".to_string(), "production".to_string())
+        };
+        let american = StatsigUser {
+            ip: Some("8.8.8.8".to_string()),
+            ..StatsigUser::new("user_id".to_string(), "production".to_string())
+        };
+        let with_country = StatsigUser {
+            country: Some("PT".to_string()),
+            ..american.clone()
+        };
+
+        let evaluator = Evaluator::new();
+        let condition = compile(&evaluator, &condition);
+        assert!(
+            eval_compiled(&evaluator, &brazilian, &condition)
+                .0
+                .fetch_from_server
+        );
+        assert!(eval_compiled(&evaluator, &with_country, &condition).0.pass);
+
+        let lookup = IpCountryLookup::from_csv("177.0.0.0/8,BR\n8.8.8.0,8.8.8.255,US").unwrap();
+        let evaluator = Evaluator::new().with_ip_country_lookup(lookup);
+        let (res, _) = eval_compiled(&evaluator, &brazilian, &condition);
+        assert!(res.pass && !res.fetch_from_server);
+        let (res, _) = eval_compiled(&evaluator, &american, &condition);
+        assert!(!res.pass && !res.fetch_from_server);
+        assert!(eval_compiled(&evaluator, &with_country, &condition).0.pass);
+    }
+
+    #[test]
+    fn test_all_evaluations_while_refreshing() {
+        fn gate(name: &str, condition: serde_json::Value) -> serde_json::Value {
+            json!({
+                "name": name,
+                "type": "feature_gate",
+                "salt": format!("{}_salt", name),
+                "enabled": true,
+                "defaultValue": false,
+                "idType": "userID",
+                "rules": [{
+                    "name": name,
+                    "id": format!("{}_rule", name),
+                    "salt": format!("{}_rule_salt", name),
+                    "passPercentage": 100,
+                    "idType": "userID",
+                    "returnValue": true,
+                    "conditions": [condition],
+                }],
+            })
+        }
+        let config_data = || -> ConfigData {
+            serde_json::from_value(json!({
+                "feature_gates": [
+                    gate("inner", json!({"type": "public", "idType": "userID"})),
+                    gate("outer", json!({
+                        "type": "pass_gate",
+                        "targetValue": "inner",
+                        "idType": "userID",
+                    })),
+                ],
+                "dynamic_configs": [{
+                    "name": "experiment",
+                    "type": "dynamic_config",
+                    "salt": "exp_salt",
+                    "enabled": true,
+                    "defaultValue": {},
+                    "idType": "userID",
+                    "rules": [],
+                }],
+                "layer_configs": [{
+                    "name": "layer",
+                    "type": "dynamic_config",
+                    "salt": "layer_salt",
+                    "enabled": true,
+                    "defaultValue": {},
+                    "idType": "userID",
+                    "rules": [{
+                        "name": "allocation",
+                        "id": "layer_rule",
+                        "salt": "layer_rule_salt",
+                        "passPercentage": 100,
+                        "idType": "userID",
+                        "returnValue": {},
+                        "configDelegate": "experiment",
+                        "conditions": [{
+                            "type": "pass_gate",
+                            "targetValue": "outer",
+                            "idType": "userID",
+                        }],
+                    }],
+                }],
+                "has_updates": true,
+            }))
+            .unwrap()
+        };
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(config_data());
+
+        // Nested gate checks and delegates must not wait on a refresh that waits on the walk
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..200 {
+                    evaluator.refresh_configs(config_data());
+                }
+            });
+            for _ in 0..200 {
+                let results = evaluator.get_all_evaluations_internal(&user);
+                assert!(results.gates["outer"].pass);
+                assert_eq!(
+                    results.layer_configs["layer"].config_delegate,
+                    Some("experiment".to_string())
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn test_experiment_respects_holdout() {
+        fn config_data(holdout_pass_percentage: u32) -> ConfigData {
+            serde_json::from_value(json!({
+                "dynamic_configs": [{
+                    "name": "experiment",
+                    "type": "dynamic_config",
+                    "entity": "experiment",
+                    "salt": "exp_salt",
+                    "enabled": true,
+                    "defaultValue": {"color": "blue"},
+                    "idType": "userID",
+                    "rules": [
+                        {
+                            "name": "holdout",
+                            "id": "holdout_rule",
+                            "salt": "holdout_rule_salt",
+                            "passPercentage": 100,
+                            "idType": "userID",
+                            "returnValue": {"color": "blue"},
+                            "conditions": [{
+                                "type": "pass_gate",
+                                "targetValue": "global_holdout",
+                                "idType": "userID",
+                            }],
+                        },
+                        {
+                            "name": "test_group",
+                            "id": "exp_rule",
+                            "salt": "exp_rule_salt",
+                            "passPercentage": 100,
+                            "idType": "userID",
+                            "returnValue": {"color": "green"},
+                            "conditions": [{"type": "public", "idType": "userID"}],
+                        },
+                    ],
+                }],
+                "feature_gates": [],
+                "holdouts": [{
+                    "name": "global_holdout",
+                    "type": "holdout",
+                    "entity": "holdout",
+                    "salt": "holdout_salt",
+                    "enabled": true,
+                    "defaultValue": false,
+                    "idType": "userID",
+                    "rules": [{
+                        "name": "holdout",
+                        "id": "global_holdout_rule",
+                        "salt": "global_holdout_rule_salt",
+                        "passPercentage": holdout_pass_percentage,
+                        "idType": "userID",
+                        "returnValue": true,
+                        "conditions": [{"type": "public", "idType": "userID"}],
+                    }],
+                }],
+                "has_updates": true,
+                "time": 1,
+            }))
+            .unwrap()
+        }
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        let evaluator = Evaluator::new();
+
+        evaluator.refresh_configs(config_data(100));
+        let res = evaluator.get_dynamic_config_internal(&user, "experiment");
+        assert_eq!(res.config_value, Some(json!({"color": "blue"})));
+        assert_eq!(res.rule_id, "holdout_rule");
+        assert_eq!(
+            res.secondary_exposures,
+            vec![HashMap::from([
+                ("gate".to_string(), "global_holdout".to_string()),
+                ("gateValue".to_string(), "true".to_string()),
+                ("ruleID".to_string(), "global_holdout_rule".to_string()),
+            ])]
+        );
+
+        evaluator.refresh_configs(config_data(0));
+        let res = evaluator.get_dynamic_config_internal(&user, "experiment");
+        assert_eq!(res.config_value, Some(json!({"color": "green"})));
+        assert_eq!(res.rule_id, "exp_rule");
+        assert_eq!(
+            res.secondary_exposures,
+            vec![HashMap::from([
+                ("gate".to_string(), "global_holdout".to_string()),
+                ("gateValue".to_string(), "false".to_string()),
+                ("ruleID".to_string(), "global_holdout_rule".to_string()),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_refresh_swaps_specs_atomically() {
+        fn config_data(version: u64) -> ConfigData {
+            let gate = |name: &str, condition: serde_json::Value| {
+                json!({
+                    "name": name,
+                    "type": "feature_gate",
+                    "salt": "salt",
+                    "enabled": true,
+                    "defaultValue": false,
+                    "idType": "userID",
+                    "rules": [{
+                        "name": name,
+                        "id": format!("{}_{}", name, version),
+                        "salt": "rule_salt",
+                        "passPercentage": 100,
+                        "idType": "userID",
+                        "returnValue": true,
+                        "conditions": [condition],
+                    }],
+                })
+            };
+            serde_json::from_value(json!({
+                "feature_gates": [
+                    gate("outer", json!({"type": "pass_gate", "targetValue": "inner", "idType": "userID"})),
+                    gate("inner", json!({"type": "public", "idType": "userID"})),
+                ],
+                "has_updates": true,
+                "time": version,
+            }))
+            .unwrap()
+        }
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(config_data(0));
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for version in 1..200 {
+                    evaluator.refresh_configs(config_data(version));
+                }
+            });
+            for _ in 0..2000 {
+                // The nested gate always comes from the same sync as the gate checking it
+                let res = evaluator.check_gate_internal(&user, "outer");
+                let version = res.rule_id.trim_start_matches("outer_");
+                assert_eq!(
+                    res.secondary_exposures[0]["ruleID"],
+                    format!("inner_{}", version)
+                );
+                // And so does the sync time reported with it
+                assert_eq!(res.config_sync_time.to_string(), version);
+            }
+        });
+    }
+
+    fn gate_chain(gates: &[(&str, Option<&str>)]) -> ConfigData {
+        let gates: Vec<_> = gates
+            .iter()
+            .map(|(name, checks)| {
+                let condition = match checks {
+                    Some(gate) => {
+                        json!({"type": "pass_gate", "targetValue": gate, "idType": "userID"})
+                    }
+                    None => json!({"type": "public", "idType": "userID"}),
+                };
+                json!({
+                    "name": name,
+                    "type": "feature_gate",
+                    "salt": "salt",
+                    "enabled": true,
+                    "defaultValue": false,
+                    "idType": "userID",
+                    "rules": [{
+                        "name": name,
+                        "id": format!("{}_rule", name),
+                        "salt": "rule_salt",
+                        "passPercentage": 100,
+                        "idType": "userID",
+                        "returnValue": true,
+                        "conditions": [condition],
+                    }],
+                })
+            })
+            .collect();
+        serde_json::from_value(json!({
+            "feature_gates": gates,
+            "has_updates": true,
+            "time": 1,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_time_dependent_results_are_flagged() {
+        let mut data = gate_chain(&[("checks_time", Some("time")), ("time", None), ("ok", None)]);
+        let gates = data.feature_gates.as_mut().unwrap();
+        gates[1].rules.as_mut().unwrap()[0].conditions = vec![serde_json::from_value(json!({
+            "type": "current_time",
+            "operator": "after",
+            "targetValue": 0,
+            "idType": "userID",
+        }))
+        .unwrap()];
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(data);
+
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        for gate in ["checks_time", "time"] {
+            let res = evaluator.check_gate_internal(&user, gate);
+            assert!(res.pass, "{}", gate);
+            assert!(res.time_dependent, "{}", gate);
+        }
+        assert!(!evaluator.check_gate_internal(&user, "ok").time_dependent);
         assert!(
             !evaluator
-                .check_gate_internal(user, &"test_gate".to_string())
-                .pass
+                .check_gate_internal(&user, "missing")
+                .time_dependent
+        );
+    }
+
+    #[test]
+    fn test_gate_cycle_fails_with_error() {
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(gate_chain(&[
+            ("a", Some("b")),
+            ("b", Some("a")),
+            ("checks_a", Some("a")),
+            ("ok", None),
+        ]));
+
+        for gate in ["a", "b", "checks_a"] {
+            let res = evaluator.check_gate_internal(&user, gate);
+            assert!(!res.pass, "{}", gate);
+            assert_eq!(res.reason, EvaluationReason::Error, "{}", gate);
+        }
+        let res = evaluator.check_gate_internal(&user, "ok");
+        assert!(res.pass);
+        assert_eq!(res.reason, EvaluationReason::LocalEvaluation);
+
+        let explanation = evaluator.explain_gate_internal(&user, "checks_a");
+        assert_eq!(explanation.outcome, ExplanationOutcome::Error);
+    }
+
+    #[test]
+    fn test_gate_nesting_depth_is_limited() {
+        let names: Vec<String> = (0..40).map(|i| format!("gate_{}", i)).collect();
+        let gates: Vec<_> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), names.get(i + 1).map(String::as_str)))
+            .collect();
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(gate_chain(&gates));
+
+        // gate_7 checks the 32 gates after it, gate_6 one more than allowed
+        let res = evaluator.check_gate_internal(&user, "gate_7");
+        assert!(res.pass);
+        assert_eq!(res.secondary_exposures.len(), 32);
+        let res = evaluator.check_gate_internal(&user, "gate_6");
+        assert!(!res.pass);
+        assert_eq!(res.reason, EvaluationReason::Error);
+    }
+
+    #[test]
+    fn test_nested_gates_are_checked_once() {
+        let gate: ConfigSpec = serde_json::from_value(json!({
+            "name": "parent",
+            "type": "feature_gate",
+            "salt": "salt",
+            "enabled": true,
+            "defaultValue": false,
+            "idType": "userID",
+            "rules": [
+                {
+                    "name": "fails",
+                    "id": "fails_rule",
+                    "salt": "fails_salt",
+                    "passPercentage": 100,
+                    "idType": "userID",
+                    "returnValue": true,
+                    "conditions": [
+                        {"type": "pass_gate", "targetValue": "child", "idType": "userID"},
+                        {"type": "fail_gate", "targetValue": "missing", "idType": "userID"},
+                        {"type": "fail_gate", "targetValue": "child", "idType": "userID"},
+                    ],
+                },
+                {
+                    "name": "passes",
+                    "id": "passes_rule",
+                    "salt": "passes_salt",
+                    "passPercentage": 100,
+                    "idType": "userID",
+                    "returnValue": true,
+                    "conditions": [
+                        {"type": "pass_gate", "targetValue": "missing", "idType": "userID"},
+                        {"type": "pass_gate", "targetValue": "child", "idType": "userID"},
+                    ],
+                },
+            ],
+        }))
+        .unwrap();
+        let mut data = gate_chain(&[("child", Some("grandchild")), ("grandchild", None)]);
+        data.feature_gates.get_or_insert_with(Vec::new).push(gate);
+        let user = StatsigUser::new("user_id".to_string(), "production".to_string());
+        let evaluator = Evaluator::new();
+        evaluator.refresh_configs(data);
+
+        let exposure = |gate: &str, value: bool, rule_id: &str| {
+            HashMap::from([
+                ("gate".to_string(), gate.to_string()),
+                ("gateValue".to_string(), value.to_string()),
+                ("ruleID".to_string(), rule_id.to_string()),
+            ])
+        };
+        let res = evaluator.check_gate_internal(&user, "parent");
+        assert!(!res.pass);
+        assert_eq!(
+            res.secondary_exposures,
+            vec![
+                exposure("grandchild", true, "grandchild_rule"),
+                exposure("child", true, "child_rule"),
+                exposure("missing", false, "default"),
+            ]
         );
     }
 }
@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::models::EvaluationReason;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EvalResult {
     pub pass: bool,
@@ -13,9 +15,17 @@ pub struct EvalResult {
     pub group: String,
     pub group_name: Option<String>,
     pub rule_id: String,
-    // pub undelegated_secondary_exposures: Option<Vec<HashMap<String, String>>>,
-    // pub config_delegate: Option<String>,
-    // pub explicit_parameters: Option<HashMap<String, bool>>,
+    pub undelegated_secondary_exposures: Option<Vec<HashMap<String, String>>>,
+    pub config_delegate: Option<String>,
+    pub explicit_parameters: Option<HashMap<String, bool>>,
+    pub reason: EvaluationReason,
+    /// Whether the user was allocated to a group of an experiment.
+    pub is_experiment_group: bool,
+    /// Milliseconds since the unix epoch of the sync of the specs it was evaluated with.
+    pub config_sync_time: u64,
+    /// Whether the result can change with the current time, only set on the result of a
+    /// top-level check.
+    pub time_dependent: bool,
 }
 
 impl Default for EvalResult {
@@ -37,6 +47,14 @@ impl EvalResult {
         Self::new(false, true)
     }
 
+    /// A failed evaluation, like one of gates that check each other in a cycle.
+    pub fn error() -> Self {
+        Self {
+            reason: EvaluationReason::Error,
+            ..Self::fail()
+        }
+    }
+
     fn new(pass: bool, fetch_from_server: bool) -> Self {
         Self {
             pass,
@@ -47,19 +65,52 @@ impl EvalResult {
             group: "default".to_owned(),
             group_name: Some("default".to_owned()),
             rule_id: "default".to_owned(),
+            undelegated_secondary_exposures: None,
+            config_delegate: None,
+            explicit_parameters: None,
+            is_experiment_group: false,
+            config_sync_time: 0,
+            time_dependent: false,
+            reason: if fetch_from_server {
+                EvaluationReason::Network
+            } else {
+                EvaluationReason::LocalEvaluation
+            },
         }
     }
 }
 
+/// Results of evaluating every spec for a user, keyed by spec name.
+#[derive(Debug, Default)]
+pub struct AllEvalResults {
+    pub gates: HashMap<String, EvalResult>,
+    pub dynamic_configs: HashMap<String, EvalResult>,
+    pub layer_configs: HashMap<String, EvalResult>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ConfigData {
     pub dynamic_configs: Option<Vec<ConfigSpec>>,
     pub feature_gates: Option<Vec<ConfigSpec>>,
     pub layer_configs: Option<Vec<ConfigSpec>>,
+    /// Holdouts shipped apart from the feature gates, they are evaluated as gates.
+    pub holdouts: Option<Vec<ConfigSpec>>,
     pub has_updates: bool,
     pub time: Option<u64>,
 }
 
+/// Metadata of an ID list as returned by the get_id_lists endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdListMetadata {
+    pub name: String,
+    pub size: u64,
+    pub url: Option<String>,
+    pub creation_time: i64,
+    #[serde(rename = "fileID")]
+    pub file_id: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConfigSpec {
@@ -70,7 +121,10 @@ pub struct ConfigSpec {
     pub rules: Option<Vec<ConfigRule>>,
     pub default_value: serde_json::Value,
     pub id_type: Option<String>,
-    // pub explicit_parameters: Option<Vec<String>>,
+    pub explicit_parameters: Option<Vec<String>>,
+    pub entity: Option<EntityType>,
+    /// Whether the experiment is running, only set for experiments.
+    pub is_active: Option<bool>,
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
@@ -78,6 +132,22 @@ pub struct ConfigSpec {
 pub enum ConfigSpecType {
     DynamicConfig,
     FeatureGate,
+    Holdout,
+    #[serde(other)]
+    Unknown,
+}
+
+/// What the spec represents in the console, gates and holdouts share the gate evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityType {
+    FeatureGate,
+    DynamicConfig,
+    Experiment,
+    Autotune,
+    Layer,
+    Holdout,
+    SegmentList,
     #[serde(other)]
     Unknown,
 }
@@ -93,7 +163,8 @@ pub struct ConfigRule {
     pub return_value: serde_json::Value, // json.RawMessage
     pub id_type: String,
     pub group_name: Option<String>,
-    // pub config_delegate: String,
+    pub config_delegate: Option<String>,
+    pub is_experiment_group: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -153,6 +224,10 @@ pub enum OperatorType {
     On,
     InSegmentList,
     NotInSegmentList,
+    ArrayContainsAny,
+    ArrayContainsNone,
+    ArrayContainsAll,
+    NotArrayContainsAll,
     #[serde(other)]
     Unknown,
 }
@@ -0,0 +1,179 @@
+/// Browser and OS information extracted from a user agent string.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UserAgent {
+    pub browser_name: Option<String>,
+    pub browser_version: Option<String>,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+}
+
+/// Returns the version right after `token`, converting `_` separators to `.`.
+fn version_after(ua: &str, token: &str) -> Option<String> {
+    let start = ua.find(token)? + token.len();
+    let version: String = ua[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == '_')
+        .map(|c| if c == '_' { '.' } else { c })
+        .collect();
+    let version = version.trim_end_matches('.');
+    if version.is_empty() {
+        None
+    } else {
+        Some(version.to_string())
+    }
+}
+
+fn parse_browser(ua: &str) -> (Option<&'static str>, Option<String>) {
+    let mobile = ua.contains("Mobile");
+    // Order matters, most browsers also advertise themselves as Chrome and Safari
+    let (name, token) = if ua.contains("Edg/") {
+        ("Edge", "Edg/")
+    } else if ua.contains("EdgA/") {
+        ("Edge Mobile", "EdgA/")
+    } else if ua.contains("EdgiOS/") {
+        ("Edge Mobile", "EdgiOS/")
+    } else if ua.contains("Edge/") {
+        ("Edge", "Edge/")
+    } else if ua.contains("OPR/") {
+        ("Opera", "OPR/")
+    } else if ua.contains("SamsungBrowser/") {
+        ("Samsung Internet", "SamsungBrowser/")
+    } else if ua.contains("CriOS/") {
+        ("Chrome Mobile iOS", "CriOS/")
+    } else if ua.contains("FxiOS/") {
+        ("Firefox iOS", "FxiOS/")
+    } else if ua.contains("Firefox/") {
+        (
+            if mobile { "Firefox Mobile" } else { "Firefox" },
+            "Firefox/",
+        )
+    } else if ua.contains("Chrome/") {
+        (if mobile { "Chrome Mobile" } else { "Chrome" }, "Chrome/")
+    } else if ua.contains("Safari/") && ua.contains("Version/") {
+        (if mobile { "Mobile Safari" } else { "Safari" }, "Version/")
+    } else if ua.contains("MSIE ") {
+        ("IE", "MSIE ")
+    } else if ua.contains("Trident/") {
+        ("IE", "rv:")
+    } else {
+        return (None, None);
+    };
+    (Some(name), version_after(ua, token))
+}
+
+fn parse_os(ua: &str) -> (Option<&'static str>, Option<String>) {
+    if ua.contains("Windows Phone") {
+        (Some("Windows Phone"), version_after(ua, "Windows Phone "))
+    } else if ua.contains("Windows") {
+        let version = version_after(ua, "Windows NT ").map(|nt| {
+            match nt.as_str() {
+                "10.0" => "10",
+                "6.3" => "8.1",
+                "6.2" => "8",
+                "6.1" => "7",
+                "6.0" => "Vista",
+                "5.1" | "5.2" => "XP",
+                other => other,
+            }
+            .to_string()
+        });
+        (Some("Windows"), version)
+    } else if ua.contains("iPhone") || ua.contains("iPad") || ua.contains("iPod") {
+        let version = version_after(ua, "iPhone OS ").or_else(|| version_after(ua, "CPU OS "));
+        (Some("iOS"), version)
+    } else if ua.contains("Android") {
+        (Some("Android"), version_after(ua, "Android "))
+    } else if ua.contains("Mac OS X") {
+        (Some("Mac OS X"), version_after(ua, "Mac OS X "))
+    } else if ua.contains("CrOS") {
+        (Some("Chrome OS"), None)
+    } else if ua.contains("Linux") {
+        (Some("Linux"), None)
+    } else {
+        (None, None)
+    }
+}
+
+impl UserAgent {
+    pub fn parse(ua: &str) -> Self {
+        let (browser_name, browser_version) = parse_browser(ua);
+        let (os_name, os_version) = parse_os(ua);
+        Self {
+            browser_name: browser_name.map(str::to_string),
+            browser_version,
+            os_name: os_name.map(str::to_string),
+            os_version,
+        }
+    }
+
+    /// Fetch a parsed field by the name used in `ua_based` conditions.
+    pub fn get_field(&self, field: &str) -> Option<&str> {
+        match field.to_ascii_lowercase().as_str() {
+            "browser_name" | "browsername" => self.browser_name.as_deref(),
+            "browser_version" | "browserversion" => self.browser_version.as_deref(),
+            "os_name" | "osname" => self.os_name.as_deref(),
+            "os_version" | "osversion" => self.os_version.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::UserAgent;
+
+    fn parsed(ua: &str) -> (String, String, String, String) {
+        let ua = UserAgent::parse(ua);
+        (
+            ua.browser_name.unwrap_or_default(),
+            ua.browser_version.unwrap_or_default(),
+            ua.os_name.unwrap_or_default(),
+            ua.os_version.unwrap_or_default(),
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        [
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/118.0.5993.88 Safari/537.36",
+                ("Chrome", "118.0.5993.88", "Windows", "10"),
+            ),
+            (
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Safari/605.1.15",
+                ("Safari", "17.0", "Mac OS X", "10.15.7"),
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1",
+                ("Mobile Safari", "16.6", "iOS", "16.6"),
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 13; SM-S908B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/112.0.0.0 Mobile Safari/537.36",
+                ("Chrome Mobile", "112.0.0.0", "Android", "13"),
+            ),
+            (
+                "Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/119.0",
+                ("Firefox", "119.0", "Linux", ""),
+            ),
+            (
+                "Mozilla/5.0 (Windows NT 6.1; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/118.0.0.0 Safari/537.36 Edg/118.0.2088.76",
+                ("Edge", "118.0.2088.76", "Windows", "7"),
+            ),
+            ("curl/8.1.2", ("", "", "", "")),
+        ]
+        .iter()
+        .for_each(|(ua, (browser, browser_version, os, os_version))| {
+            assert_eq!(
+                parsed(ua),
+                (
+                    browser.to_string(),
+                    browser_version.to_string(),
+                    os.to_string(),
+                    os_version.to_string()
+                ),
+                "{}",
+                ua
+            );
+        });
+    }
+}
@@ -0,0 +1,157 @@
+use std::cmp::{max, Ordering};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PreReleaseIdentifier {
+    Numeric(u64),
+    AlphaNumeric(String),
+}
+
+impl PartialOrd for PreReleaseIdentifier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PreReleaseIdentifier {
+    /// Numeric identifiers compare numerically and always have lower precedence than
+    /// alphanumeric ones, which compare lexically.
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Numeric(a), Self::Numeric(b)) => a.cmp(b),
+            (Self::Numeric(_), Self::AlphaNumeric(_)) => Ordering::Less,
+            (Self::AlphaNumeric(_), Self::Numeric(_)) => Ordering::Greater,
+            (Self::AlphaNumeric(a), Self::AlphaNumeric(b)) => a.cmp(b),
+        }
+    }
+}
+
+/// A version as used by the `version_*` operators, ordered by semver precedence.
+///
+/// The numeric part can have any number of components, missing ones count as 0 so
+/// `1.2` equals `1.2.0`. Build metadata after `+` is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    core: Vec<u64>,
+    pre_release: Vec<PreReleaseIdentifier>,
+}
+
+impl Version {
+    /// Parses a version string.
+    ///
+    /// In strict mode any component that isn't a valid number or pre-release identifier
+    /// makes the whole version invalid, otherwise non-numeric components count as 0.
+    pub fn parse(version: &str, strict: bool) -> Option<Self> {
+        let version = version.trim();
+        let version = match version.find('+') {
+            Some(index) => &version[..index],
+            None => version,
+        };
+        let (core, pre_release) = match version.find('-') {
+            Some(index) => (&version[..index], Some(&version[index + 1..])),
+            None => (version, None),
+        };
+
+        let core = core
+            .split('.')
+            .map(|part| match part.parse::<u64>() {
+                Ok(n) => Some(n),
+                Err(_) if strict => None,
+                Err(_) => Some(0),
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let pre_release = match pre_release {
+            None => vec![],
+            Some(pre_release) => pre_release
+                .split('.')
+                .map(|id| {
+                    let valid =
+                        !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+                    if strict && !valid {
+                        return None;
+                    }
+                    Some(match id.parse::<u64>() {
+                        Ok(n) => PreReleaseIdentifier::Numeric(n),
+                        Err(_) => PreReleaseIdentifier::AlphaNumeric(id.to_string()),
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?,
+        };
+
+        Some(Self { core, pre_release })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = max(self.core.len(), other.core.len());
+        let core = (0..len)
+            .map(|i| {
+                let a = self.core.get(i).unwrap_or(&0);
+                let b = other.core.get(i).unwrap_or(&0);
+                a.cmp(b)
+            })
+            .find(|cmp| cmp.is_ne())
+            .unwrap_or(Ordering::Equal);
+        if core.is_ne() {
+            return core;
+        }
+
+        // A pre-release has lower precedence than the release itself
+        match (self.pre_release.is_empty(), other.pre_release.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => self.pre_release.cmp(&other.pre_release),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+
+    use super::Version;
+
+    fn compare(v1: &str, v2: &str, strict: bool) -> Option<Ordering> {
+        Some(Version::parse(v1, strict)?.cmp(&Version::parse(v2, strict)?))
+    }
+
+    #[test]
+    fn test_precedence() {
+        [
+            ("1.2.3", "1.2.3", Ordering::Equal),
+            ("1.2", "1.2.0", Ordering::Equal),
+            ("1.10.0", "1.9.0", Ordering::Greater),
+            ("1.2.3-beta", "1.2.3", Ordering::Less),
+            ("1.2.3", "1.2.3-rc.1", Ordering::Greater),
+            ("1.0.0-alpha", "1.0.0-alpha.1", Ordering::Less),
+            ("1.0.0-alpha.1", "1.0.0-alpha.beta", Ordering::Less),
+            ("1.0.0-alpha.beta", "1.0.0-beta", Ordering::Less),
+            ("1.0.0-beta.2", "1.0.0-beta.11", Ordering::Less),
+            ("1.0.0-rc.1", "1.0.0", Ordering::Less),
+            ("1.2.3+build.5", "1.2.3+build.7", Ordering::Equal),
+            ("1.2.3-beta+build", "1.2.3-beta", Ordering::Equal),
+        ]
+        .iter()
+        .for_each(|(v1, v2, expected)| {
+            assert_eq!(compare(v1, v2, false), Some(*expected), "{} vs {}", v1, v2);
+            assert_eq!(compare(v1, v2, true), Some(*expected), "{} vs {}", v1, v2);
+        });
+    }
+
+    #[test]
+    fn test_unparseable() {
+        assert_eq!(compare("1.x", "1.0", false), Some(Ordering::Equal));
+        assert_eq!(compare("1.x", "1.0", true), None);
+        assert_eq!(compare("", "0", true), None);
+        assert_eq!(compare("1.2.3-", "1.2.3", true), None);
+        assert_eq!(compare("1.2.3-be_ta", "1.2.3", true), None);
+    }
+}
@@ -1,8 +1,9 @@
-use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
 use reqwest::{
     header,
     header::{HeaderMap, HeaderName, HeaderValue},
-    Client, ClientBuilder, StatusCode,
+    Client, ClientBuilder, RequestBuilder, Response, StatusCode,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tokio::time::Duration;
@@ -10,11 +11,16 @@ use tokio_retry::{
     strategy::{jitter, ExponentialBackoff},
     Retry,
 };
+use tracing::{event, Level};
 
 use crate::{
-    evaluator::models::ConfigData,
+    error::{from_slice, Result, StatsigError},
+    evaluator::{
+        id_lists::IdLists,
+        models::{ConfigData, IdListMetadata},
+    },
     models::{
-        ExperimentExposurePost, StatsigConfig, StatsigEvent, StatsigMetadata, StatsigPost,
+        ExperimentExposurePost, ExposureEvent, Layer, StatsigConfig, StatsigMetadata, StatsigPost,
         StatsigUser,
     },
 };
@@ -26,6 +32,10 @@ const RUST_SDK: &str = "rust-server";
 // TODO: Proper versioning
 const RUST_SDK_VERSION: &str = "0.0.1";
 
+/// Statuses the events endpoints answer with when events are accepted.
+const SUCCESS_STATUSES: [StatusCode; 3] =
+    [StatusCode::OK, StatusCode::CREATED, StatusCode::ACCEPTED];
+
 /// The environment variable to change the default timeout for statsig requests.
 const STATSIG_TIMEOUT_MS: &str = "STATSIG_TIMEOUT_MS";
 
@@ -53,6 +63,38 @@ fn create_http_connection_client(key: &str) -> Client {
         .expect("should be able to build the http client")
 }
 
+/// Sends the request, failing when the response status isn't one of `ok`.
+async fn send(
+    endpoint: &'static str,
+    request: RequestBuilder,
+    ok: &[StatusCode],
+) -> Result<Response> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| StatsigError::from_request(endpoint, e))?;
+    check_status(endpoint, response, ok)
+}
+
+fn check_status(endpoint: &'static str, response: Response, ok: &[StatusCode]) -> Result<Response> {
+    if ok.contains(&response.status()) {
+        Ok(response)
+    } else {
+        Err(StatsigError::HttpStatus {
+            endpoint,
+            status: response.status().as_u16(),
+        })
+    }
+}
+
+async fn read_json<T: DeserializeOwned>(endpoint: &'static str, response: Response) -> Result<T> {
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| StatsigError::from_request(endpoint, e))?;
+    from_slice(&bytes)
+}
+
 #[derive(Clone)]
 pub struct StatsigHttpClient {
     api_key: String,
@@ -102,19 +144,9 @@ impl StatsigHttpClient {
             gate_name: gate,
         };
 
-        let response = self.http_client.post(url).json(&body).send().await;
-        let res = match response {
-            Ok(result) => match result.status() {
-                StatusCode::OK => Ok(result),
-                err => Err(anyhow!("statsig error: {}", err)),
-            },
-            Err(err) => Err(anyhow!("failed to send request: {}", err)),
-        }?;
-
-        let parsed = match res.json::<CheckGateResponse>().await {
-            Ok(parsed) => Ok(parsed),
-            Err(err) => Err(anyhow!("error parsing statsig gate response: {}", err)),
-        }?;
+        let request = self.http_client.post(url).json(&body);
+        let res = send("check_gate", request, &[StatusCode::OK]).await?;
+        let parsed: CheckGateResponse = read_json("check_gate", res).await?;
 
         Ok(parsed.value)
     }
@@ -137,24 +169,15 @@ impl StatsigHttpClient {
             config_name: config,
         };
 
-        let response = self.http_client.post(url).json(&body).send().await;
-        let res = match response {
-            Ok(result) => match result.status() {
-                StatusCode::OK => Ok(result),
-                err => Err(anyhow!("statsig error: {}", err)),
-            },
-            Err(err) => Err(anyhow!("failed to send request: {}", err)),
-        }?;
+        let request = self.http_client.post(url).json(&body);
+        let res = send("get_config", request, &[StatusCode::OK]).await?;
 
         #[derive(Deserialize)]
         struct GetConfigResponse<E> {
             value: E,
         }
 
-        let parsed = match res.json::<GetConfigResponse<T>>().await {
-            Ok(parsed) => Ok(parsed),
-            Err(err) => Err(anyhow!("error parsing: {}", err)),
-        }?;
+        let parsed: GetConfigResponse<T> = read_json("get_config", res).await?;
 
         Ok(parsed.value)
     }
@@ -177,56 +200,61 @@ impl StatsigHttpClient {
             config_name: config,
         };
 
-        let response = self.http_client.post(url).json(&body).send().await;
-        let res = match response {
-            Ok(result) => match result.status() {
-                StatusCode::OK => Ok(result),
-                err => Err(anyhow!("statsig error: {}", err)),
-            },
-            Err(err) => Err(anyhow!("failed to send request: {}", err)),
-        }?;
+        let request = self.http_client.post(url).json(&body);
+        let res = send("get_config", request, &[StatusCode::OK]).await?;
+        read_json("get_config", res).await
+    }
 
-        let parsed = match res.json::<StatsigConfig<T>>().await {
-            Ok(parsed) => Ok(parsed),
-            Err(err) => Err(anyhow!("error parsing: {}", err)),
-        }?;
+    pub async fn get_layer(&self, layer: String, user: StatsigUser) -> Result<Layer> {
+        #[derive(Serialize)]
+        struct GetLayerBody {
+            user: StatsigUser,
+            #[serde(rename = "layerName")]
+            layer_name: String,
+        }
 
-        Ok(parsed)
+        let url = format!("{}/get_layer", self.base_url);
+        let body = GetLayerBody {
+            user,
+            layer_name: layer,
+        };
+
+        let request = self.http_client.post(url).json(&body);
+        let res = send("get_layer", request, &[StatusCode::OK]).await?;
+        read_json("get_layer", res).await
     }
 
     pub async fn log_event(&self, statsig_post: &StatsigPost) -> Result<()> {
+        self.post_events(&statsig_post.events).await
+    }
+
+    /// Logs the exposures queued by the client.
+    pub async fn log_exposures(&self, events: &[ExposureEvent]) -> Result<()> {
+        self.post_events(events).await
+    }
+
+    async fn post_events<E: Serialize>(&self, events: &[E]) -> Result<()> {
         let url = format!("{}/log_event", self.events_url);
 
         #[derive(Serialize)]
         #[serde(rename_all = "camelCase")]
-        struct PostBody<'a> {
-            events: &'a [StatsigEvent],
+        struct PostBody<'a, E> {
+            events: &'a [E],
             sdk_type: &'a str,
             sdk_version: &'a str,
         }
         let body = PostBody {
-            events: &statsig_post.events,
+            events,
             sdk_type: RUST_SDK,
             sdk_version: RUST_SDK_VERSION,
         };
         // TODO: Retry
-        let response = self.http_client.post(url).json(&body).send().await;
-
-        match response {
-            Ok(result) => match result.status() {
-                StatusCode::OK | StatusCode::CREATED | StatusCode::ACCEPTED => Ok(result),
-                err => Err(anyhow!("statsig error: {}", err)),
-            },
-            Err(err) => Err(anyhow!("failed to send request: {}", err)),
-        }?;
+        let request = self.http_client.post(url).json(&body);
+        send("log_event", request, &SUCCESS_STATUSES).await?;
 
         Ok(())
     }
 
-    pub async fn log_event_internal(&self, statsig_post: StatsigPost) -> Result<()> {
-        self.log_event(&statsig_post).await
-    }
-
     /// Logs custom experiment exposures to Statsig.
     /// This is the correct way to log experiment exposures for holdout tracking.
     pub async fn log_custom_exposure(&self, exposure_post: &ExperimentExposurePost) -> Result<()> {
@@ -245,16 +273,104 @@ impl StatsigHttpClient {
             statsig_metadata: exposure_post.statsig_metadata.as_ref(),
         };
 
-        let response = self.http_client.post(url).json(&body).send().await;
+        let request = self.http_client.post(url).json(&body);
+        send("log_custom_exposure", request, &SUCCESS_STATUSES).await?;
 
-        match response {
-            Ok(result) => match result.status() {
-                StatusCode::OK | StatusCode::CREATED | StatusCode::ACCEPTED => Ok(result),
-                err => Err(anyhow!("statsig error logging custom exposure: {}", err)),
+        Ok(())
+    }
+
+    /// Fetches the metadata of every ID list, keyed by list name.
+    pub async fn get_id_lists(&self) -> Result<HashMap<String, IdListMetadata>> {
+        let url = format!("{}/get_id_lists", self.base_url);
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct PostBody {
+            statsig_metadata: StatsigMetadata,
+        }
+        let body = PostBody {
+            statsig_metadata: StatsigMetadata {
+                sdk_type: RUST_SDK.to_string(),
+                sdk_version: RUST_SDK_VERSION.to_string(),
             },
-            Err(err) => Err(anyhow!("failed to send custom exposure request: {}", err)),
-        }?;
+        };
 
+        let request = self.http_client.post(url).json(&body);
+        let res = send("get_id_lists", request, &[StatusCode::OK]).await?;
+        read_json("get_id_lists", res).await
+    }
+
+    /// Downloads an ID list file starting at byte `offset`.
+    ///
+    /// Returns the offset the contents actually start at, which is zero when the server
+    /// ignored the range and sent the whole file.
+    pub async fn download_id_list(&self, url: &str, offset: u64) -> Result<(u64, String)> {
+        let request = self
+            .http_client
+            .get(url)
+            .header(header::RANGE, format!("bytes={}-", offset));
+        let res = send(
+            "id list download",
+            request,
+            &[StatusCode::PARTIAL_CONTENT, StatusCode::OK],
+        )
+        .await?;
+        let offset = if res.status() == StatusCode::PARTIAL_CONTENT {
+            offset
+        } else {
+            0
+        };
+        let contents = res
+            .text()
+            .await
+            .map_err(|e| StatsigError::from_request("id list download", e))?;
+        Ok((offset, contents))
+    }
+
+    /// Brings the ID lists up to date, downloading only the bytes appended since the last sync.
+    ///
+    /// A list is downloaded from scratch when its file id changes or it was recreated, and
+    /// lists that are no longer returned by the API are dropped.
+    pub async fn sync_id_lists(&self, id_lists: &IdLists) -> Result<()> {
+        let lists = self.get_id_lists().await?;
+
+        for (name, remote) in lists.iter() {
+            let url = match &remote.url {
+                Some(url) => url,
+                None => continue,
+            };
+            let offset = match id_lists.metadata(name) {
+                Some(local)
+                    if local.file_id == remote.file_id
+                        && local.creation_time >= remote.creation_time =>
+                {
+                    if remote.size <= local.size {
+                        continue;
+                    }
+                    local.size
+                }
+                _ => 0,
+            };
+
+            let (offset, contents) = match self.download_id_list(url, offset).await {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    event!(Level::ERROR, "Failed to download id list {}: {}", name, e);
+                    continue;
+                }
+            };
+            if !contents.is_empty() && !contents.starts_with(['+', '-']) {
+                event!(
+                    Level::ERROR,
+                    "Invalid contents downloaded for id list {}",
+                    name
+                );
+                continue;
+            }
+            id_lists.apply_download(remote, offset, &contents);
+        }
+
+        id_lists.retain(&lists.keys().collect());
         Ok(())
     }
 
@@ -272,17 +388,10 @@ impl StatsigHttpClient {
         let response = Retry::spawn(retry_strategy, || async {
             self.http_client.get(url.clone()).send().await
         })
-        .await;
-        let res = match response {
-            Ok(result) => match result.status() {
-                StatusCode::OK => Ok(result),
-                status => Err(anyhow!("statsig error fetching source: {}", status)),
-            },
-            Err(err) => Err(anyhow!("failed to send request to fetch state: {}", err)),
-        }?;
-        res.json::<ConfigData>()
-            .await
-            .map_err(|e| anyhow!("error parsing state response: {}", e))
+        .await
+        .map_err(|e| StatsigError::from_request("download_config_specs", e))?;
+        let res = check_status("download_config_specs", response, &[StatusCode::OK])?;
+        read_json("download_config_specs", res).await
     }
 }
 
@@ -293,7 +402,13 @@ mod test {
     use crate::models::{StatsigEvent, StatsigUser};
 
     use super::*;
-    use httptest::{matchers::request, responders::json_encoded, Expectation, Server};
+    use crate::evaluator::id_lists::hash_unit_id;
+    use httptest::{
+        all_of,
+        matchers::{contains, request},
+        responders::{json_encoded, status_code},
+        Expectation, Server,
+    };
     use serde_json::json;
 
     #[tokio::test]
@@ -375,6 +490,259 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_errors() -> Result<()> {
+        let http_server = Server::run();
+        http_server.expect(
+            Expectation::matching(request::method_path("POST", "/check_gate"))
+                .respond_with(status_code(500)),
+        );
+        http_server.expect(
+            Expectation::matching(request::method_path("POST", "/get_config")).respond_with(
+                json_encoded(json!({
+                    "value": {"merchant_id": 1234}
+                })),
+            ),
+        );
+
+        #[derive(Debug, Deserialize)]
+        struct ConfigTest {
+            #[allow(dead_code)]
+            merchant_id: String,
+        }
+
+        let client = StatsigHttpClient::new(
+            "something".to_string(),
+            Some(format!("http://{}", http_server.addr())),
+            None,
+            None,
+        );
+        let user = StatsigUser::new("1234".to_string(), "test".to_string());
+
+        let err = client
+            .check_gate("gate".to_string(), user.clone())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                StatsigError::HttpStatus {
+                    endpoint: "check_gate",
+                    status: 500
+                }
+            ),
+            "{:?}",
+            err
+        );
+
+        let err = client
+            .get_dynamic_config::<ConfigTest>("dynamic".to_string(), user.clone())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, StatsigError::Deserialization { path, .. } if path == "value.merchant_id"),
+            "{:?}",
+            err
+        );
+
+        // Nothing listens on the port anymore
+        let addr = http_server.addr();
+        drop(http_server);
+        let client = StatsigHttpClient::new(
+            "something".to_string(),
+            Some(format!("http://{}", addr)),
+            None,
+            None,
+        );
+        let err = client
+            .check_gate("gate".to_string(), user)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                StatsigError::Transport {
+                    endpoint: "check_gate",
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_layer() -> Result<()> {
+        let http_server = Server::run();
+        http_server.expect(
+            Expectation::matching(request::method_path("POST", "/get_layer")).respond_with(
+                json_encoded(json!({
+                    "name": "layer_name",
+                    "value": {
+                        "button_color": "blue",
+                        "max_items": 10
+                    },
+                    "rule_id": "rule_id",
+                    "group_name": "Control",
+                })),
+            ),
+        );
+
+        let client = StatsigHttpClient::new(
+            "something".to_string(),
+            Some(format!("http://{}", http_server.addr())),
+            None,
+            None,
+        );
+
+        let user = StatsigUser::new("1234".to_string(), "test".to_string());
+        let result = client.get_layer("layer_name".to_string(), user).await?;
+
+        assert_eq!("layer_name", result.name);
+        assert_eq!("rule_id", result.rule_id);
+        assert_eq!(Some("Control".to_string()), result.group_name);
+        assert_eq!(
+            Some("blue".to_string()),
+            result.get::<String>("button_color")
+        );
+        assert_eq!(10, result.get_or("max_items", 0));
+        assert_eq!(None, result.get::<String>("missing"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_id_lists() -> Result<()> {
+        let http_server = Server::run();
+        let list_url = format!("http://{}/id_lists/list_1", http_server.addr());
+        http_server.expect(
+            Expectation::matching(request::method_path("POST", "/get_id_lists")).respond_with(
+                json_encoded(json!({
+                    "list_1": {
+                        "name": "list_1",
+                        "size": 20,
+                        "url": list_url,
+                        "creationTime": 1,
+                        "fileID": "file_1",
+                    }
+                })),
+            ),
+        );
+        http_server.expect(
+            Expectation::matching(request::method_path("GET", "/id_lists/list_1"))
+                .respond_with(status_code(200).body("+7w9rbTSf\n+IasbRzXb\n")),
+        );
+
+        let client = StatsigHttpClient::new(
+            "something".to_string(),
+            Some(format!("http://{}", http_server.addr())),
+            None,
+            None,
+        );
+
+        let lists = client.get_id_lists().await?;
+        let list = lists.get("list_1").expect("list should exist");
+        assert_eq!(list.size, 20);
+        assert_eq!(list.creation_time, 1);
+        assert_eq!(list.file_id, Some("file_1".to_string()));
+
+        let (offset, contents) = client
+            .download_id_list(list.url.as_ref().expect("url should exist"), 0)
+            .await?;
+        assert_eq!(offset, 0);
+        assert_eq!(contents, "+7w9rbTSf\n+IasbRzXb\n");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_id_lists_with_ranges() -> Result<()> {
+        fn expect_id_list(server: &Server, size: u64, file_id: &str, range: &str, body: String) {
+            server.expect(
+                Expectation::matching(request::method_path("POST", "/get_id_lists")).respond_with(
+                    json_encoded(json!({
+                        "list": {
+                            "name": "list",
+                            "size": size,
+                            "url": format!("http://{}/list", server.addr()),
+                            "creationTime": 1,
+                            "fileID": file_id,
+                        }
+                    })),
+                ),
+            );
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", "/list"),
+                    request::headers(contains(("range", range.to_string()))),
+                ])
+                .respond_with(status_code(206).body(body)),
+            );
+        }
+
+        let mut http_server = Server::run();
+        let client = StatsigHttpClient::new(
+            "something".to_string(),
+            Some(format!("http://{}", http_server.addr())),
+            None,
+            None,
+        );
+        let id_lists = IdLists::new();
+
+        // Initial download of the whole file
+        expect_id_list(
+            &http_server,
+            20,
+            "file_1",
+            "bytes=0-",
+            format!("+{}\n+{}\n", hash_unit_id("1"), hash_unit_id("2")),
+        );
+        client.sync_id_lists(&id_lists).await?;
+        assert!(id_lists.contains("list", "1"));
+        assert!(id_lists.contains("list", "2"));
+        http_server.verify_and_clear();
+
+        // Only the appended bytes are requested
+        expect_id_list(
+            &http_server,
+            30,
+            "file_1",
+            "bytes=20-",
+            format!("-{}\n", hash_unit_id("1")),
+        );
+        client.sync_id_lists(&id_lists).await?;
+        assert!(!id_lists.contains("list", "1"));
+        assert!(id_lists.contains("list", "2"));
+        assert_eq!(id_lists.metadata("list").map(|m| m.size), Some(30));
+        http_server.verify_and_clear();
+
+        // A new file is downloaded from scratch
+        expect_id_list(
+            &http_server,
+            10,
+            "file_2",
+            "bytes=0-",
+            format!("+{}\n", hash_unit_id("3")),
+        );
+        client.sync_id_lists(&id_lists).await?;
+        assert!(!id_lists.contains("list", "2"));
+        assert!(id_lists.contains("list", "3"));
+        http_server.verify_and_clear();
+
+        // Deleted lists are dropped
+        http_server.expect(
+            Expectation::matching(request::method_path("POST", "/get_id_lists"))
+                .respond_with(json_encoded(json!({}))),
+        );
+        client.sync_id_lists(&id_lists).await?;
+        assert!(!id_lists.contains("list", "3"));
+        assert_eq!(id_lists.metadata("list"), None);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_log_event() -> Result<()> {
         let http_server = Server::run();
@@ -5,8 +5,20 @@
 //!
 //! Reference: https://docs.statsig.com/http-api
 mod client;
+mod error;
+mod eval_cache;
 mod evaluator;
 mod http;
+mod overrides;
 
 pub mod models;
 pub use crate::client::Client;
+pub use crate::error::{Result, StatsigError};
+
+/// Internals the benchmarks drive directly, only built with the `bench` feature and not part
+/// of the public API. Run them with `cargo bench --features bench`.
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod __bench {
+    pub use crate::evaluator::{models::ConfigData, Evaluator};
+}
@@ -15,6 +15,9 @@ async fn main() {
             events_url: None,
             disable_cache: false,
             config_sync_interval: Some(Duration::from_secs(5)),
+            geoip_database_path: None,
+            strict_version_comparison: false,
+            evaluation_cache_size: None,
         },
     )
     .await
@@ -1,6 +1,6 @@
-use std::time::Duration;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use std::collections::HashMap;
 
@@ -11,6 +11,8 @@ pub struct StatsigConfig<T> {
     pub group_name: Option<String>,
     pub rule_id: String,
     pub group: String,
+    #[serde(default)]
+    pub evaluation_details: Option<EvaluationDetails>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +24,318 @@ pub struct StatsigExperiment<T> {
     pub group: String,
     #[serde(default)]
     pub secondary_exposures: Vec<SecondaryExposure>,
+    #[serde(default)]
+    pub evaluation_details: Option<EvaluationDetails>,
+}
+
+/// Where the value of a check came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvaluationReason {
+    /// Evaluated by the statsig API.
+    Network,
+    /// Evaluated locally with the synced specs.
+    LocalEvaluation,
+    /// The gate or config isn't in the synced specs, the default is returned.
+    Unrecognized,
+    /// The gate or config is disabled in the console, the default is returned.
+    Disabled,
+    /// Set through one of the `Client::override_*` methods.
+    LocalOverride,
+    /// Evaluated with specs provided up front instead of synced.
+    Bootstrap,
+    /// No specs were loaded yet, the default is returned.
+    Uninitialized,
+    /// The evaluation failed, the default is returned.
+    Error,
+}
+
+/// Where a value came from, together with the age of the specs it was evaluated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationDetails {
+    pub reason: EvaluationReason,
+    /// Milliseconds since the unix epoch of the last config sync, 0 if there was none.
+    pub config_sync_time: u64,
+    /// Milliseconds since the unix epoch when the client loaded its first specs, 0 if it
+    /// didn't yet.
+    pub init_time: u64,
+}
+
+/// A gate evaluated for a user, see `Client::get_feature_gate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureGate {
+    pub name: String,
+    pub value: bool,
+    pub rule_id: String,
+    pub group_name: Option<String>,
+    pub evaluation_details: EvaluationDetails,
+}
+
+/// The result of a synchronous check, like `Client::check_gate_local`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalResult<T> {
+    /// Evaluated locally, or set through one of the `Client::override_*` methods.
+    Value(T),
+    /// Only the statsig API can evaluate it, because the cache is disabled or a condition
+    /// can't be evaluated locally. The async methods make that request.
+    NeedsNetwork,
+}
+
+impl<T> LocalResult<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> LocalResult<U> {
+        match self {
+            LocalResult::Value(value) => LocalResult::Value(f(value)),
+            LocalResult::NeedsNetwork => LocalResult::NeedsNetwork,
+        }
+    }
+
+    /// The value, `None` when it needs the network.
+    pub fn value(self) -> Option<T> {
+        match self {
+            LocalResult::Value(value) => Some(value),
+            LocalResult::NeedsNetwork => None,
+        }
+    }
+}
+
+/// Every gate, config and layer evaluated for a user, see `Client::get_all_evaluations`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllEvaluations {
+    pub feature_gates: HashMap<String, SpecEvaluation>,
+    pub dynamic_configs: HashMap<String, SpecEvaluation>,
+    pub layer_configs: HashMap<String, SpecEvaluation>,
+}
+
+/// The result of one spec in `AllEvaluations`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecEvaluation {
+    /// The gate's boolean for gates, the config or layer value otherwise.
+    pub value: serde_json::Value,
+    pub rule_id: String,
+    pub group_name: Option<String>,
+    #[serde(default)]
+    pub secondary_exposures: Vec<SecondaryExposure>,
+}
+
+/// How spec names are hashed in a client initialize response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// Base64 encoded sha256 of the name, what the client SDKs expect by default.
+    #[default]
+    Sha256,
+    /// The djb2 hash of the name as an unsigned 32 bit integer.
+    Djb2,
+    /// Names are sent as they are.
+    None,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClientInitializeResponseOptions {
+    pub hash: HashAlgorithm,
+}
+
+/// The response of the statsig `/initialize` endpoint, used to bootstrap client SDKs.
+///
+/// Built by `Client::get_client_initialize_response`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientInitializeResponse {
+    pub feature_gates: HashMap<String, GateInitializeValue>,
+    pub dynamic_configs: HashMap<String, ConfigInitializeValue>,
+    pub layer_configs: HashMap<String, LayerInitializeValue>,
+    pub has_updates: bool,
+    /// Milliseconds since the unix epoch of the specs the values were evaluated with.
+    pub time: u64,
+    pub hash_used: HashAlgorithm,
+    pub user: StatsigUser,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GateInitializeValue {
+    pub name: String,
+    pub value: bool,
+    pub rule_id: String,
+    pub id_type: Option<String>,
+    pub secondary_exposures: Vec<HashMap<String, String>>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigInitializeValue {
+    pub name: String,
+    pub value: serde_json::Value,
+    pub rule_id: String,
+    pub group: String,
+    pub group_name: Option<String>,
+    pub is_device_based: bool,
+    pub id_type: Option<String>,
+    pub secondary_exposures: Vec<HashMap<String, String>>,
+    /// Only set for experiments.
+    pub is_user_in_experiment: Option<bool>,
+    /// Only set for experiments.
+    pub is_experiment_active: Option<bool>,
+    /// Only set for experiments.
+    pub explicit_parameters: Option<Vec<String>>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerInitializeValue {
+    pub name: String,
+    pub value: serde_json::Value,
+    pub rule_id: String,
+    pub group: String,
+    pub group_name: Option<String>,
+    pub is_device_based: bool,
+    pub secondary_exposures: Vec<HashMap<String, String>>,
+    pub undelegated_secondary_exposures: Vec<HashMap<String, String>>,
+    pub explicit_parameters: Vec<String>,
+    /// The hashed name of the experiment the layer delegated to.
+    pub allocated_experiment_name: Option<String>,
+    /// Only set when the layer delegated to an experiment.
+    pub is_user_in_experiment: Option<bool>,
+    /// Only set when the layer delegated to an experiment.
+    pub is_experiment_active: Option<bool>,
+}
+
+/// Step by step trace of how a gate or config was evaluated for a user.
+///
+/// Returned by `Client::explain_gate` and `Client::explain_config`, no exposures are logged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Explanation {
+    pub name: String,
+    pub pass: bool,
+    pub value: Option<serde_json::Value>,
+    pub rule_id: String,
+    pub group_name: Option<String>,
+    /// Rules in the order they were visited, rules after the matching one are not visited.
+    pub rules: Vec<RuleExplanation>,
+    pub outcome: ExplanationOutcome,
+}
+
+impl Explanation {
+    pub(crate) fn new(name: &str, outcome: ExplanationOutcome) -> Self {
+        Self {
+            name: name.to_string(),
+            pass: false,
+            value: None,
+            rule_id: "default".to_string(),
+            group_name: None,
+            rules: vec![],
+            outcome,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleExplanation {
+    pub rule_id: String,
+    pub name: String,
+    /// Whether every condition of the rule passed.
+    pub pass: bool,
+    pub conditions: Vec<ConditionExplanation>,
+    /// The user's bucket in 0..10000, only computed when the conditions pass.
+    pub bucket: Option<u64>,
+    pub pass_percentage: f64,
+    /// The experiment a layer rule delegated to.
+    pub delegate: Option<Box<Explanation>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionExplanation {
+    pub condition_type: String,
+    pub operator: Option<String>,
+    pub field: Option<String>,
+    /// The value resolved from the user, or the gate result for pass_gate/fail_gate.
+    pub user_value: serde_json::Value,
+    pub target_value: Option<serde_json::Value>,
+    pub pass: bool,
+    pub fetch_from_server: bool,
+}
+
+/// How an evaluation reached its final result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExplanationOutcome {
+    /// The gate or config doesn't exist in the synced specs.
+    Unrecognized,
+    /// The spec is disabled and returns its default.
+    Disabled,
+    /// A rule matched and the user is within its pass percentage.
+    RuleMatched,
+    /// A rule matched but the user's bucket is outside its pass percentage.
+    RolloutFailed,
+    /// A layer rule matched and handed the result to an experiment.
+    Delegated,
+    /// No rule matched, the default value is returned.
+    NoRuleMatched,
+    /// A condition can't be evaluated locally, the API would be called.
+    FetchFromServer,
+    /// A local override applies to the user.
+    LocalOverride,
+    /// The evaluation failed, like for gates that check each other in a cycle, the default
+    /// is returned.
+    Error,
+}
+
+/// The users a local override applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OverrideTarget {
+    AllUsers,
+    UserId(String),
+    /// A custom id, e.g. `CustomId("companyID".to_string(), "1234".to_string())`.
+    CustomId(String, String),
+}
+
+/// Called with the name of each layer parameter that's read, to log its exposure.
+#[derive(Clone)]
+pub(crate) struct ParameterExposureLogger(pub(crate) Arc<dyn Fn(&str) + Send + Sync>);
+
+impl std::fmt::Debug for ParameterExposureLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ParameterExposureLogger")
+    }
+}
+
+/// A layer evaluated for a user. Parameters are read through the typed getters, which log an
+/// exposure for each parameter of a locally evaluated layer. Reading `value` logs nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    #[serde(default)]
+    pub value: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub rule_id: String,
+    pub group_name: Option<String>,
+    /// The experiment the layer delegated allocation to, if any.
+    pub allocated_experiment_name: Option<String>,
+    #[serde(default)]
+    pub secondary_exposures: Vec<SecondaryExposure>,
+    #[serde(default)]
+    pub undelegated_secondary_exposures: Vec<SecondaryExposure>,
+    #[serde(skip)]
+    pub(crate) exposure_logger: Option<ParameterExposureLogger>,
+}
+
+impl Layer {
+    /// Returns the parameter deserialized as `T`, or `None` if it is missing or has another type.
+    pub fn get<T: DeserializeOwned>(&self, param: &str) -> Option<T> {
+        let value = self.value.get(param)?;
+        if let Some(logger) = &self.exposure_logger {
+            (logger.0)(param);
+        }
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Returns the parameter deserialized as `T`, falling back to `default`.
+    pub fn get_or<T: DeserializeOwned>(&self, param: &str, default: T) -> T {
+        self.get(param).unwrap_or(default)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -91,14 +405,21 @@ pub struct StatsigEvent {
     pub time: String, // unix timestamp
     pub user: StatsigUser,
     pub metadata: HashMap<String, String>,
-    // secondary_exposures
 }
 
-#[skip_serializing_none]
-#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+/// An exposure the client logs itself, along with the exposures of the gates the result
+/// depended on.
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
+pub(crate) struct ExposureEvent {
+    #[serde(flatten)]
+    pub(crate) event: StatsigEvent,
+    pub(crate) secondary_exposures: Vec<HashMap<String, String>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+#[serde(from = "SerializedUser", into = "SerializedUser")]
 pub struct StatsigUser {
-    #[serde(rename = "userID")]
     pub user_id: String,
     pub email: Option<String>,
     pub ip: Option<String>,
@@ -107,35 +428,137 @@ pub struct StatsigUser {
     pub locale: Option<String>,
     pub app_version: Option<String>,
     pub custom: Option<HashMap<String, String>>,
+    /// Custom attributes that aren't strings, like numbers, booleans or arrays. Conditions on
+    /// a field look them up after `custom`. They are sent to the API as part of `custom`.
+    pub custom_values: Option<HashMap<String, serde_json::Value>>,
     pub private_atributes: Option<HashMap<String, String>>,
-    #[serde(rename = "customIDs")]
     pub custom_ids: Option<HashMap<String, String>>,
     pub statsig_environment: StatsigEnvironment,
 }
 
+/// The user as the statsig API reads it, with `custom_values` merged into `custom`.
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedUser {
+    #[serde(rename = "userID")]
+    user_id: String,
+    email: Option<String>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    country: Option<String>,
+    locale: Option<String>,
+    app_version: Option<String>,
+    custom: Option<HashMap<String, serde_json::Value>>,
+    private_atributes: Option<HashMap<String, String>>,
+    #[serde(rename = "customIDs")]
+    custom_ids: Option<HashMap<String, String>>,
+    statsig_environment: StatsigEnvironment,
+}
+
+impl From<StatsigUser> for SerializedUser {
+    fn from(user: StatsigUser) -> Self {
+        let custom = match (user.custom, user.custom_values) {
+            (None, None) => None,
+            (custom, values) => {
+                // `custom` is looked up first, so its entries win over typed values
+                let mut merged = values.unwrap_or_default();
+                merged.extend(
+                    custom
+                        .into_iter()
+                        .flatten()
+                        .map(|(k, v)| (k, serde_json::Value::String(v))),
+                );
+                Some(merged)
+            }
+        };
+        SerializedUser {
+            user_id: user.user_id,
+            email: user.email,
+            ip: user.ip,
+            user_agent: user.user_agent,
+            country: user.country,
+            locale: user.locale,
+            app_version: user.app_version,
+            custom,
+            private_atributes: user.private_atributes,
+            custom_ids: user.custom_ids,
+            statsig_environment: user.statsig_environment,
+        }
+    }
+}
+
+impl From<SerializedUser> for StatsigUser {
+    fn from(user: SerializedUser) -> Self {
+        let (custom, custom_values) = match user.custom {
+            None => (None, None),
+            Some(custom) => {
+                let mut strings = HashMap::new();
+                let mut values = HashMap::new();
+                for (k, v) in custom {
+                    match v {
+                        serde_json::Value::String(s) => {
+                            strings.insert(k, s);
+                        }
+                        v => {
+                            values.insert(k, v);
+                        }
+                    }
+                }
+                if values.is_empty() {
+                    (Some(strings), None)
+                } else {
+                    ((!strings.is_empty()).then_some(strings), Some(values))
+                }
+            }
+        };
+        StatsigUser {
+            user_id: user.user_id,
+            email: user.email,
+            ip: user.ip,
+            user_agent: user.user_agent,
+            country: user.country,
+            locale: user.locale,
+            app_version: user.app_version,
+            custom,
+            custom_values,
+            private_atributes: user.private_atributes,
+            custom_ids: user.custom_ids,
+            statsig_environment: user.statsig_environment,
+        }
+    }
+}
+
 /// Options to use when creating the client, they will override default values, if they exist.
 ///
 /// The default value for api_url is https://api.statsig.com/v1
 /// The default value for config_sync_interval is 15s
+///
+/// When geoip_database_path is set, `ip_based` country conditions are resolved locally from
+/// that CSV file (`start_ip,end_ip,country` or `network/prefix,country` per line) instead of
+/// calling the API.
+///
+/// When strict_version_comparison is set, `version_*` conditions fail if either version can't
+/// be parsed instead of treating the unparseable parts as 0.
+///
+/// When evaluation_cache_size is set, up to that many results of local evaluations are kept,
+/// one per gate, config or layer and user, so checking them again skips the evaluation.
+/// Exposures are still logged for every check. The cache is cleared whenever new configs or id
+/// lists are synced. Results that depend on the current time, through a current_time condition
+/// of the spec or of a gate or experiment it uses, are evaluated every time.
+#[derive(Default)]
 pub struct StatsigOptions {
     pub api_url: Option<String>,
     pub cdn_url: Option<String>,
     pub events_url: Option<String>,
     pub disable_cache: bool,
     pub config_sync_interval: Option<Duration>,
+    pub geoip_database_path: Option<PathBuf>,
+    pub strict_version_comparison: bool,
+    pub evaluation_cache_size: Option<usize>,
 }
 
 impl StatsigOptions {
-    pub fn default() -> Self {
-        Self {
-            api_url: None,
-            cdn_url: None,
-            disable_cache: false,
-            config_sync_interval: None,
-            events_url: None,
-        }
-    }
-
     pub fn cache_disabled() -> Self {
         Self {
             api_url: None,
@@ -143,6 +566,9 @@ impl StatsigOptions {
             disable_cache: true,
             config_sync_interval: None,
             events_url: None,
+            geoip_database_path: None,
+            strict_version_comparison: false,
+            evaluation_cache_size: None,
         }
     }
 }
@@ -174,6 +600,7 @@ impl StatsigUser {
             locale: None,
             app_version: None,
             custom: None,
+            custom_values: None,
             private_atributes: None,
             custom_ids: None,
             statsig_environment: StatsigEnvironment { tier },
@@ -197,34 +624,59 @@ impl StatsigUser {
         self.user_id.clone()
     }
 
+    /// Sets a custom attribute that isn't a string, see `custom_values`.
+    pub fn with_custom_value(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.custom_values
+            .get_or_insert_with(HashMap::new)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Fetch a user field as a string, typed custom values are converted to json.
     pub fn get_field(&self, field: &String) -> String {
-        let empty = "".to_string();
-        match field.to_ascii_lowercase().as_str() {
-            "userid" | "user_id" => self.user_id.clone(),
-            "email" => self.email.as_ref().unwrap_or(&empty).clone(),
-            "ip" | "ipaddress" | "ip_address" => self.ip.as_ref().unwrap_or(&empty).clone(),
-            "useragent" | "user_agent" => self.user_agent.as_ref().unwrap_or(&empty).clone(),
-            "country" => self.country.as_ref().unwrap_or(&empty).clone(),
-            "locale" => self.locale.as_ref().unwrap_or(&empty).clone(),
-            "appversion" | "app_version" => self.app_version.as_ref().unwrap_or(&empty).clone(),
+        match self.get_field_value(field) {
+            serde_json::Value::Null => "".to_string(),
+            serde_json::Value::String(s) => s,
+            v => v.to_string(),
+        }
+    }
+
+    /// Fetch a user field, falling back to the custom attributes, the typed custom values and
+    /// the private attributes.
+    ///
+    /// Missing fields are returned as null.
+    pub(crate) fn get_field_value(&self, field: &String) -> serde_json::Value {
+        let known = match field.to_ascii_lowercase().as_str() {
+            "userid" | "user_id" => Some(&self.user_id),
+            "email" => self.email.as_ref(),
+            "ip" | "ipaddress" | "ip_address" => self.ip.as_ref(),
+            "useragent" | "user_agent" => self.user_agent.as_ref(),
+            "country" => self.country.as_ref(),
+            "locale" => self.locale.as_ref(),
+            "appversion" | "app_version" => self.app_version.as_ref(),
             _ => {
-                let mut ret = "".to_string();
-                if let Some(custom) = &self.custom {
-                    if let Some(v) = custom.get(field) {
-                        ret = v.clone();
-                    } else if let Some(v) = custom.get(&field.to_ascii_lowercase()) {
-                        ret = v.clone();
-                    }
-                } else if let Some(private) = &self.private_atributes {
-                    if let Some(v) = private.get(field) {
-                        ret = v.clone();
-                    } else if let Some(v) = private.get(&field.to_ascii_lowercase()) {
-                        ret = v.clone();
-                    }
-                }
-                ret
+                let lower = field.to_ascii_lowercase();
+                let string = |attributes: &Option<HashMap<String, String>>| {
+                    let attributes = attributes.as_ref()?;
+                    let v = attributes.get(field).or_else(|| attributes.get(&lower))?;
+                    Some(serde_json::Value::String(v.clone()))
+                };
+                return string(&self.custom)
+                    .or_else(|| {
+                        let values = self.custom_values.as_ref()?;
+                        values.get(field).or_else(|| values.get(&lower)).cloned()
+                    })
+                    .or_else(|| string(&self.private_atributes))
+                    .unwrap_or(serde_json::Value::Null);
             }
-        }
+        };
+        known.map_or(serde_json::Value::Null, |v| {
+            serde_json::Value::String(v.clone())
+        })
     }
 }
 
@@ -232,6 +684,8 @@ impl StatsigUser {
 mod test {
     use std::collections::HashMap;
 
+    use serde_json::json;
+
     use crate::models::StatsigEnvironment;
 
     use super::StatsigUser;
@@ -285,6 +739,7 @@ mod test {
             locale: None,
             app_version: None,
             custom: Some(HashMap::from([("custom1".to_string(), "val1".to_string())])),
+            custom_values: None,
             private_atributes: None,
             custom_ids: None,
             statsig_environment: StatsigEnvironment {
@@ -298,5 +753,38 @@ mod test {
         );
         assert_eq!("192.168.0.1".to_string(), user.get_field(&"ip".to_string()));
         assert_eq!("val1".to_string(), user.get_field(&"custom1".to_string()));
+
+        let user = user
+            .with_custom_value("custom2", 2)
+            .with_custom_value("custom1", false);
+        assert_eq!(json!("val1"), user.get_field_value(&"custom1".to_string()));
+        assert_eq!(json!(2), user.get_field_value(&"custom2".to_string()));
+        assert_eq!("2".to_string(), user.get_field(&"custom2".to_string()));
+        assert_eq!(json!(null), user.get_field_value(&"custom3".to_string()));
+        assert_eq!(json!(null), user.get_field_value(&"country".to_string()));
+    }
+
+    #[test]
+    fn test_custom_values_serialize_as_custom() {
+        let user = StatsigUser::new("user_id".to_string(), "prod".to_string())
+            .with_custom_value("level", 7)
+            .with_custom_value("beta", true);
+        let user = StatsigUser {
+            custom: Some(HashMap::from([("plan".to_string(), "pro".to_string())])),
+            ..user
+        };
+
+        let serialized = serde_json::to_value(&user).unwrap();
+        assert_eq!(
+            serialized,
+            json!({
+                "userID": "user_id",
+                "custom": {"plan": "pro", "level": 7, "beta": true},
+                "statsigEnvironment": {"tier": "prod"},
+            })
+        );
+
+        let deserialized: StatsigUser = serde_json::from_value(serialized).unwrap();
+        assert_eq!(deserialized, user);
     }
 }
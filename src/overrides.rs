@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use crate::models::{OverrideTarget, StatsigUser};
+
+/// Values set locally for gates and configs, they take precedence over any evaluation.
+#[derive(Default)]
+pub struct Overrides {
+    gates: HashMap<String, HashMap<OverrideTarget, bool>>,
+    configs: HashMap<String, HashMap<OverrideTarget, serde_json::Value>>,
+}
+
+/// Finds the override for the user, a user id wins over a custom id, which wins over all users.
+/// Between custom ids, the id type that sorts first wins.
+fn find<'a, T>(targets: &'a HashMap<OverrideTarget, T>, user: &StatsigUser) -> Option<&'a T> {
+    targets
+        .get(&OverrideTarget::UserId(user.user_id.clone()))
+        .or_else(|| {
+            let mut custom_ids: Vec<_> = user.custom_ids.iter().flatten().collect();
+            custom_ids.sort_unstable_by_key(|(id_type, _)| *id_type);
+            custom_ids.into_iter().find_map(|(id_type, id)| {
+                targets.get(&OverrideTarget::CustomId(id_type.clone(), id.clone()))
+            })
+        })
+        .or_else(|| targets.get(&OverrideTarget::AllUsers))
+}
+
+fn remove<T>(
+    overrides: &mut HashMap<String, HashMap<OverrideTarget, T>>,
+    name: &str,
+    target: &OverrideTarget,
+) {
+    if let Some(targets) = overrides.get_mut(name) {
+        targets.remove(target);
+        if targets.is_empty() {
+            overrides.remove(name);
+        }
+    }
+}
+
+impl Overrides {
+    pub fn set_gate(&mut self, gate: String, value: bool, target: OverrideTarget) {
+        self.gates.entry(gate).or_default().insert(target, value);
+    }
+
+    pub fn set_config(&mut self, config: String, value: serde_json::Value, target: OverrideTarget) {
+        self.configs
+            .entry(config)
+            .or_default()
+            .insert(target, value);
+    }
+
+    pub fn remove_gate(&mut self, gate: &str, target: &OverrideTarget) {
+        remove(&mut self.gates, gate, target);
+    }
+
+    pub fn remove_config(&mut self, config: &str, target: &OverrideTarget) {
+        remove(&mut self.configs, config, target);
+    }
+
+    pub fn clear(&mut self) {
+        self.gates.clear();
+        self.configs.clear();
+    }
+
+    pub fn gate(&self, gate: &str, user: &StatsigUser) -> Option<bool> {
+        self.gates
+            .get(gate)
+            .and_then(|targets| find(targets, user))
+            .copied()
+    }
+
+    pub fn config(&self, config: &str, user: &StatsigUser) -> Option<&serde_json::Value> {
+        self.configs
+            .get(config)
+            .and_then(|targets| find(targets, user))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::Overrides;
+    use crate::models::{OverrideTarget, StatsigUser};
+
+    #[test]
+    fn test_override_precedence() {
+        let mut overrides = Overrides::default();
+        let user = StatsigUser {
+            custom_ids: Some(HashMap::from([(
+                "companyID".to_string(),
+                "company".to_string(),
+            )])),
+            ..StatsigUser::new("user".to_string(), "production".to_string())
+        };
+        let other_user = StatsigUser::new("other".to_string(), "production".to_string());
+
+        overrides.set_gate("gate".to_string(), true, OverrideTarget::AllUsers);
+        overrides.set_gate(
+            "gate".to_string(),
+            false,
+            OverrideTarget::CustomId("companyID".to_string(), "company".to_string()),
+        );
+        assert_eq!(overrides.gate("gate", &user), Some(false));
+        assert_eq!(overrides.gate("gate", &other_user), Some(true));
+
+        overrides.set_gate(
+            "gate".to_string(),
+            true,
+            OverrideTarget::UserId("user".to_string()),
+        );
+        assert_eq!(overrides.gate("gate", &user), Some(true));
+
+        overrides.remove_gate("gate", &OverrideTarget::AllUsers);
+        assert_eq!(overrides.gate("gate", &other_user), None);
+        assert_eq!(overrides.gate("other_gate", &user), None);
+
+        overrides.set_config(
+            "config".to_string(),
+            json!({"value": "override"}),
+            OverrideTarget::AllUsers,
+        );
+        assert_eq!(
+            overrides.config("config", &user),
+            Some(&json!({"value": "override"}))
+        );
+        overrides.clear();
+        assert_eq!(overrides.config("config", &user), None);
+        assert_eq!(overrides.gate("gate", &user), None);
+    }
+
+    #[test]
+    fn test_custom_id_precedence() {
+        let id_types: Vec<String> = (0..32).map(|i| format!("id_{:02}", i)).collect();
+        let mut overrides = Overrides::default();
+        for (i, id_type) in id_types.iter().enumerate().skip(1) {
+            overrides.set_config(
+                "config".to_string(),
+                json!(i),
+                OverrideTarget::CustomId(id_type.clone(), "id".to_string()),
+            );
+        }
+
+        // Maps built separately iterate in different orders, the first id type still wins
+        for _ in 0..10 {
+            let user = StatsigUser {
+                custom_ids: Some(
+                    id_types
+                        .iter()
+                        .map(|id_type| (id_type.clone(), "id".to_string()))
+                        .collect(),
+                ),
+                ..StatsigUser::new("user".to_string(), "production".to_string())
+            };
+            assert_eq!(overrides.config("config", &user), Some(&json!(1)));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde_json::json;
+
+use statsig_rdp::models::{EvaluationReason, StatsigUser};
+
+use common::create_client;
+
+pub mod common;
+
+#[tokio::test]
+async fn test_get_all_evaluations() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser {
+        custom: Some(HashMap::from([(
+            "secondaryId".to_owned(),
+            "secretid".to_string(),
+        )])),
+        ..StatsigUser::new("1239".to_string(), "production".to_string())
+    };
+
+    let evaluations = client.get_all_evaluations(&user)?;
+
+    let gate = &evaluations.feature_gates["test_gate"];
+    assert_eq!(gate.value, json!(true));
+    assert_eq!(gate.rule_id, "public1");
+    assert_eq!(
+        evaluations.feature_gates["test_segment_gate"].value,
+        json!(true)
+    );
+    // Holdouts and segments are only evaluated through the specs that use them
+    assert!(!evaluations.feature_gates.contains_key("test_holdout"));
+    assert!(!evaluations
+        .feature_gates
+        .contains_key("test_untagged_holdout"));
+    assert!(!evaluations
+        .feature_gates
+        .contains_key("segment:test_segment"));
+
+    let config = &evaluations.dynamic_configs["test_dynamic_config"];
+    assert_eq!(config.value, json!({"value": "1239"}));
+    assert_eq!(config.rule_id, "id_1239_name");
+    assert_eq!(config.group_name.as_deref(), Some("test_1239"));
+
+    let layer = &evaluations.layer_configs["test_layer"];
+    assert_eq!(layer.value, json!({"button_color": "red", "max_items": 20}));
+
+    // The snapshot serializes as a single document
+    let serialized = serde_json::to_value(&evaluations)?;
+    assert_eq!(
+        serialized["dynamicConfigs"]["test_dynamic_config"]["ruleId"],
+        json!("id_1239_name")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_holdouts_are_not_gates() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    for holdout in ["test_holdout", "test_untagged_holdout"] {
+        let gate = client
+            .clone()
+            .get_feature_gate(holdout.to_string(), user.clone())
+            .await?;
+        assert!(!gate.value, "{}", holdout);
+        assert_eq!(
+            gate.evaluation_details.reason,
+            EvaluationReason::Unrecognized,
+            "{}",
+            holdout
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde_json::json;
+
+use statsig_rdp::models::{ClientInitializeResponseOptions, HashAlgorithm, StatsigUser};
+
+use common::create_client;
+
+pub mod common;
+
+#[tokio::test]
+async fn test_client_initialize_response() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    let response = client.get_client_initialize_response(
+        &user,
+        ClientInitializeResponseOptions {
+            hash: HashAlgorithm::None,
+        },
+    )?;
+    assert!(response.has_updates);
+    assert_eq!(response.hash_used, HashAlgorithm::None);
+    let gate = &response.feature_gates["test_gate"];
+    assert!(gate.value);
+    assert_eq!(gate.rule_id, "public1");
+    let layer = &response.layer_configs["test_layer"];
+    assert_eq!(layer.value, json!({"button_color": "red", "max_items": 20}));
+    assert_eq!(
+        response.dynamic_configs["test_dynamic_config"].value,
+        json!({"value": "DEFAULT"})
+    );
+    for holdout in ["test_holdout", "test_untagged_holdout"] {
+        assert!(!response.feature_gates.contains_key(holdout), "{}", holdout);
+    }
+
+    // Names are hashed with sha256 by default
+    let response =
+        client.get_client_initialize_response(&user, ClientInitializeResponseOptions::default())?;
+    let hashed = "AoZS0F06Ub+W2ONx+94rPTS7MRxuxa+GnXro5Q1uaGY=";
+    assert_eq!(response.feature_gates[hashed].name, hashed);
+
+    let serialized = serde_json::to_value(&response)?;
+    assert_eq!(serialized["hash_used"], json!("sha256"));
+    assert_eq!(
+        serialized["feature_gates"][hashed]["rule_id"],
+        json!("public1")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_client_initialize_response_omits_private_attributes() -> Result<()> {
+    let client = create_client().await;
+    let mut user = StatsigUser::new("1239".to_string(), "production".to_string());
+    user.email = Some("user@statsig.com".to_string());
+    user.private_atributes = Some(HashMap::from([("secret".to_string(), "value".to_string())]));
+
+    let response =
+        client.get_client_initialize_response(&user, ClientInitializeResponseOptions::default())?;
+    assert_eq!(response.user.private_atributes, None);
+    assert_eq!(response.user.email, user.email);
+
+    let serialized = serde_json::to_value(&response)?;
+    assert!(serialized["user"].get("privateAtributes").is_none());
+    assert!(!serialized.to_string().contains("secret"));
+
+    Ok(())
+}
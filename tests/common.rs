@@ -1,8 +1,19 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
-use httptest::{matchers::request, responders::json_encoded, Expectation, Server};
+use httptest::{
+    all_of,
+    matchers::{json_decoded, request},
+    responders::{json_encoded, status_code},
+    Expectation, Server,
+};
 use serde_json::json;
-use statsig_rdp::{models::StatsigOptions, Client};
+use statsig_rdp::{
+    models::{StatsigEvent, StatsigOptions},
+    Client,
+};
 
 pub fn expect_fetch_config_specs(server: &Server) {
     server.expect(
@@ -112,6 +123,126 @@ pub fn expect_fetch_config_specs(server: &Server) {
                             "idType": "userid",
                         }],
                     }],
+                },
+                {
+                    "name": "test_segment_gate",
+                    "type": "feature_gate",
+                    "salt": "segment_salt",
+                    "enabled": true,
+                    "defaultValue": false,
+                    "idType": "userID",
+                    "rules": [{
+                        "name": "in_segment",
+                        "groupName": "in_segment",
+                        "id": "in_segment_rule",
+                        "salt": "segment_rule_salt",
+                        "passPercentage": 100,
+                        "idType": "userID",
+                        "returnValue": true,
+                        "conditions": [{
+                            "type": "unit_id",
+                            "operator": "in_segment_list",
+                            "targetValue": "test_list",
+                            "idType": "userID",
+                        }],
+                    }],
+                },
+                {
+                    "name": "segment:test_segment",
+                    "type": "feature_gate",
+                    "salt": "segment_salt",
+                    "enabled": true,
+                    "defaultValue": false,
+                    "idType": "userID",
+                    "entity": "segment_list",
+                    "rules": [{
+                        "name": "public",
+                        "id": "segment_public",
+                        "salt": "segment_public_salt",
+                        "passPercentage": 100,
+                        "idType": "userID",
+                        "returnValue": true,
+                        "conditions": [{
+                            "type": "public",
+                            "idType": "userID",
+                        }],
+                    }],
+                },
+                {
+                    "name": "test_untagged_holdout",
+                    "type": "holdout",
+                    "salt": "untagged_holdout_salt",
+                    "enabled": true,
+                    "defaultValue": false,
+                    "idType": "userID",
+                    "rules": [{
+                        "name": "public",
+                        "id": "untagged_holdout_public",
+                        "salt": "untagged_holdout_public_salt",
+                        "passPercentage": 100,
+                        "idType": "userID",
+                        "returnValue": true,
+                        "conditions": [{
+                            "type": "public",
+                            "idType": "userID",
+                        }],
+                    }],
+                }
+            ],
+            "holdouts": [
+                {
+                    "name": "test_holdout",
+                    "type": "holdout",
+                    "salt": "holdout_salt",
+                    "enabled": true,
+                    "defaultValue": false,
+                    "idType": "userID",
+                    "entity": "holdout",
+                    "rules": [{
+                        "name": "public",
+                        "id": "holdout_public",
+                        "salt": "holdout_public_salt",
+                        "passPercentage": 100,
+                        "idType": "userID",
+                        "returnValue": true,
+                        "conditions": [{
+                            "type": "public",
+                            "idType": "userID",
+                        }],
+                    }],
+                }
+            ],
+            "layer_configs": [
+                {
+                    "name": "test_layer",
+                    "type": "dynamic_config",
+                    "salt": "layer_salt",
+                    "enabled": true,
+                    "defaultValue": {
+                        "button_color": "blue",
+                        "max_items": 10
+                    },
+                    "idType": "userID",
+                    "entity": "layer",
+                    "rules": [{
+                        "name": "layer_1239",
+                        "groupName": "layer_1239_group",
+                        "id": "layer_rule_1239",
+                        "salt": "layer_rule_salt",
+                        "passPercentage": 100,
+                        "idType": "userID",
+                        "returnValue": {
+                            "button_color": "red",
+                            "max_items": 20
+                        },
+                        "conditions": [{
+                            "type": "user_field",
+                            "targetValue": ["1239"],
+                            "operator": "any",
+                            "field": "userID",
+                            "idType": "userID",
+                        }],
+                    }],
                 }
             ],
             "has_updates": true,
@@ -120,6 +251,28 @@ pub fn expect_fetch_config_specs(server: &Server) {
     );
 }
 
+pub fn expect_id_lists(server: &Server) {
+    server.expect(
+        Expectation::matching(request::method_path("POST", "/get_id_lists"))
+            .times(..)
+            .respond_with(json_encoded(json!({
+                "test_list": {
+                    "name": "test_list",
+                    "size": 20,
+                    "url": format!("http://{}/id_lists/test_list", server.addr()),
+                    "creationTime": 1,
+                    "fileID": "file_1",
+                }
+            }))),
+    );
+    // Contains the hashed ids of users 1239 and 1241, 1241 was removed afterwards
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/id_lists/test_list"))
+            .times(..)
+            .respond_with(status_code(200).body("++XNQEB4a\n+Ggh4XUiX\n-Ggh4XUiX\n")),
+    );
+}
+
 pub fn expect_log_emission(server: &Server) {
     server.expect(
         Expectation::matching(request::method_path("POST", "/log_event"))
@@ -128,26 +281,76 @@ pub fn expect_log_emission(server: &Server) {
     );
 }
 
+/// An event posted to `/log_event`, exposures logged by the client also carry the exposures of
+/// the gates the result depended on.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedEvent {
+    #[serde(flatten)]
+    pub event: StatsigEvent,
+    #[serde(default)]
+    pub secondary_exposures: Vec<HashMap<String, String>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct LoggedEvents {
+    events: Vec<LoggedEvent>,
+}
+
+/// Records the events posted to `/log_event`.
+pub fn record_log_emission(server: &Server) -> Arc<Mutex<Vec<LoggedEvent>>> {
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/log_event"),
+            request::body(json_decoded(move |post: &LoggedEvents| {
+                recorded.lock().unwrap().extend(post.events.iter().cloned());
+                true
+            })),
+        ])
+        .times(..)
+        .respond_with(json_encoded(json!({}))),
+    );
+    events
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub struct ConfigValue {
     pub value: String,
 }
 
 pub async fn create_client() -> Arc<Client> {
+    create_client_with(|_| {}).await
+}
+
+/// Creates a client against the test server, with the options changed by `configure`.
+pub async fn create_client_with(configure: impl FnOnce(&mut StatsigOptions)) -> Arc<Client> {
     let http_server = Server::run();
     expect_fetch_config_specs(&http_server);
+    expect_id_lists(&http_server);
     expect_log_emission(&http_server);
+    connect_client(&http_server, configure).await
+}
+
+/// Creates a client against `server`, which already expects the requests made on creation.
+pub async fn connect_client(
+    server: &Server,
+    configure: impl FnOnce(&mut StatsigOptions),
+) -> Arc<Client> {
+    let mut options = StatsigOptions {
+        api_url: Some(format!("http://{}", server.addr())),
+        cdn_url: Some(format!("http://{}", server.addr())),
+        events_url: Some(format!("http://{}", server.addr())),
+        disable_cache: false,
+        config_sync_interval: None,
+        geoip_database_path: None,
+        strict_version_comparison: false,
+        evaluation_cache_size: None,
+    };
+    configure(&mut options);
 
-    Client::new(
-        "api_key".to_string(),
-        StatsigOptions {
-            api_url: Some(format!("http://{}", http_server.addr())),
-            cdn_url: Some(format!("http://{}", http_server.addr())),
-            events_url: Some(format!("http://{}", http_server.addr())),
-            disable_cache: false,
-            config_sync_interval: None,
-        },
-    )
-    .await
-    .expect("should be able to create statsig client")
+    Client::new("api_key".to_string(), options)
+        .await
+        .expect("should be able to create statsig client")
 }
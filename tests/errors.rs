@@ -0,0 +1,55 @@
+use anyhow::Result;
+
+use serde::Deserialize;
+use statsig_rdp::{models::StatsigUser, StatsigError};
+
+use common::{create_client, create_client_with, ConfigValue};
+
+pub mod common;
+
+#[tokio::test]
+async fn test_client_errors() -> Result<()> {
+    let client = create_client().await;
+
+    let user = StatsigUser::new(String::new(), "production".to_string());
+    let err = client
+        .clone()
+        .check_gate("test_gate".to_string(), user)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StatsigError::InvalidUser(_)), "{:?}", err);
+
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+    let err = client
+        .clone()
+        .get_dynamic_config::<ConfigValue>("missing_config".to_string(), user.clone())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, StatsigError::UnknownSpec(name) if name == "missing_config"),
+        "{:?}",
+        err
+    );
+
+    #[derive(Debug, Deserialize)]
+    struct WrongValue {
+        #[allow(dead_code)]
+        value: u64,
+    }
+    let err = client
+        .clone()
+        .get_dynamic_config::<WrongValue>("test_dynamic_config".to_string(), user.clone())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, StatsigError::Deserialization { path, .. } if path == "value"),
+        "{:?}",
+        err
+    );
+
+    let client = create_client_with(|options| options.disable_cache = true).await;
+    let err = client.get_all_evaluations(&user).unwrap_err();
+    assert!(matches!(err, StatsigError::Uninitialized), "{:?}", err);
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use statsig_rdp::models::StatsigUser;
+
+use common::{create_client, create_client_with, ConfigValue};
+
+pub mod common;
+
+#[tokio::test]
+async fn test_cached_evaluations_match_uncached() -> Result<()> {
+    let client = create_client().await;
+    // Small enough that the users below evict each other
+    let cached_client = create_client_with(|options| options.evaluation_cache_size = Some(4)).await;
+
+    let users = [
+        StatsigUser::new("1239".to_string(), "production".to_string()),
+        StatsigUser::new("1240".to_string(), "production".to_string()),
+        StatsigUser {
+            custom: Some(HashMap::from([(
+                "secondaryId".to_owned(),
+                "secretid".to_string(),
+            )])),
+            email: Some("something@example.com".to_string()),
+            ..StatsigUser::new("1239".to_string(), "production".to_string())
+        },
+    ];
+    for _ in 0..3 {
+        for user in &users {
+            for gate in ["test_gate", "test_segment_gate", "missing_gate"] {
+                let expected = client
+                    .clone()
+                    .get_feature_gate(gate.to_string(), user.clone())
+                    .await?;
+                let gate = cached_client
+                    .clone()
+                    .get_feature_gate(gate.to_string(), user.clone())
+                    .await?;
+                assert_eq!(
+                    (gate.value, gate.rule_id),
+                    (expected.value, expected.rule_id)
+                );
+            }
+
+            let expected: ConfigValue = client
+                .clone()
+                .get_dynamic_config("test_dynamic_config".to_string(), user.clone())
+                .await?;
+            let val: ConfigValue = cached_client
+                .clone()
+                .get_dynamic_config("test_dynamic_config".to_string(), user.clone())
+                .await?;
+            assert_eq!(val.value, expected.value);
+
+            let expected = client
+                .clone()
+                .get_layer("test_layer".to_string(), user.clone())
+                .await?;
+            let layer = cached_client
+                .clone()
+                .get_layer("test_layer".to_string(), user.clone())
+                .await?;
+            assert_eq!(layer.rule_id, expected.rule_id);
+            assert_eq!(
+                layer.get::<String>("button_color"),
+                expected.get::<String>("button_color")
+            );
+        }
+    }
+
+    Ok(())
+}
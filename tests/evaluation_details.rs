@@ -0,0 +1,64 @@
+use anyhow::Result;
+
+use statsig_rdp::models::{EvaluationReason, StatsigConfig, StatsigUser};
+
+use common::{create_client, ConfigValue};
+
+pub mod common;
+
+#[tokio::test]
+async fn test_gate_evaluation_details() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    let gate = client
+        .clone()
+        .get_feature_gate("test_gate".to_string(), user.clone())
+        .await?;
+    assert!(gate.value);
+    assert_eq!(gate.rule_id, "public1");
+    assert_eq!(
+        gate.evaluation_details.reason,
+        EvaluationReason::LocalEvaluation
+    );
+    assert!(gate.evaluation_details.init_time > 0);
+
+    let gate = client
+        .get_feature_gate("missing_gate".to_string(), user)
+        .await?;
+    assert!(!gate.value);
+    assert_eq!(
+        gate.evaluation_details.reason,
+        EvaluationReason::Unrecognized
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_config_evaluation_details() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    // A default coming from the spec is told apart from an unknown config
+    let config: StatsigConfig<ConfigValue> = client
+        .clone()
+        .get_config("test_dynamic_config".to_string(), user.clone())
+        .await?;
+    assert_eq!(config.value.map(|v| v.value), Some("DEFAULT".to_string()));
+    assert_eq!(
+        config.evaluation_details.map(|d| d.reason),
+        Some(EvaluationReason::LocalEvaluation)
+    );
+
+    let config: StatsigConfig<ConfigValue> = client
+        .get_config("missing_config".to_string(), user)
+        .await?;
+    assert!(config.value.is_none());
+    assert_eq!(
+        config.evaluation_details.map(|d| d.reason),
+        Some(EvaluationReason::Unrecognized)
+    );
+
+    Ok(())
+}
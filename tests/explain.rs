@@ -0,0 +1,68 @@
+use anyhow::Result;
+use serde_json::json;
+
+use statsig_rdp::models::{ExplanationOutcome, OverrideTarget, StatsigUser};
+
+use common::create_client;
+
+pub mod common;
+
+#[tokio::test]
+async fn test_explain_config() -> Result<()> {
+    let client = create_client().await;
+
+    // The first rule matches on the email but has a 0% pass percentage
+    let user = StatsigUser {
+        email: Some("test@example.com".to_string()),
+        ..StatsigUser::new("1239".to_string(), "production".to_string())
+    };
+    let explanation = client.explain_config("test_dynamic_config", &user)?;
+    assert_eq!(explanation.outcome, ExplanationOutcome::RolloutFailed);
+    assert!(!explanation.pass);
+    assert_eq!(explanation.value, Some(json!({"value": "DEFAULT"})));
+    assert_eq!(explanation.rules.len(), 1);
+    let rule = &explanation.rules[0];
+    assert_eq!(rule.rule_id, "id_reject_email");
+    assert!(rule.pass);
+    assert!(rule.bucket.is_some());
+    assert_eq!(rule.conditions.len(), 1);
+    let condition = &rule.conditions[0];
+    assert_eq!(condition.condition_type, "user_field");
+    assert_eq!(condition.operator.as_deref(), Some("any"));
+    assert_eq!(condition.user_value, json!("test@example.com"));
+    assert!(condition.pass);
+
+    // Both rules are visited and fail, so the default is returned
+    let user = StatsigUser::new("1240".to_string(), "production".to_string());
+    let explanation = client.explain_config("test_dynamic_config", &user)?;
+    assert_eq!(explanation.outcome, ExplanationOutcome::NoRuleMatched);
+    assert_eq!(explanation.rules.len(), 2);
+    assert!(explanation
+        .rules
+        .iter()
+        .all(|r| !r.pass && r.bucket.is_none()));
+    assert_eq!(explanation.rules[1].conditions[0].user_value, json!("1240"));
+
+    let explanation = client.explain_config("missing_config", &user)?;
+    assert_eq!(explanation.outcome, ExplanationOutcome::Unrecognized);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_explain_gate() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    let explanation = client.explain_gate("test_gate", &user)?;
+    assert_eq!(explanation.outcome, ExplanationOutcome::RuleMatched);
+    assert!(explanation.pass);
+    assert_eq!(explanation.rule_id, "public1");
+
+    client.override_gate("test_gate".to_string(), false, OverrideTarget::AllUsers);
+    let explanation = client.explain_gate("test_gate", &user)?;
+    assert_eq!(explanation.outcome, ExplanationOutcome::LocalOverride);
+    assert!(!explanation.pass);
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use anyhow::Result;
+
+use statsig_rdp::models::StatsigUser;
+
+use common::create_client;
+
+pub mod common;
+
+#[tokio::test]
+async fn test_check_gate_in_segment_list() -> Result<()> {
+    let client = create_client().await;
+
+    // 1239 is in the list, 1241 was added and then removed
+    for (user_id, expected) in [("1239", true), ("1240", false), ("1241", false)] {
+        let user = StatsigUser::new(user_id.to_string(), "production".to_string());
+        let pass = client
+            .clone()
+            .check_gate("test_segment_gate".to_string(), user)
+            .await
+            .expect("should succeed");
+        assert_eq!(pass, expected, "{}", user_id);
+    }
+
+    Ok(())
+}
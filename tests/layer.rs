@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use httptest::{matchers::request, responders::json_encoded, Expectation, Server};
+use serde_json::json;
+
+use statsig_rdp::models::StatsigUser;
+
+use common::{connect_client, create_client, expect_id_lists, record_log_emission};
+
+pub mod common;
+
+#[tokio::test]
+async fn test_get_layer() -> Result<()> {
+    let client = create_client().await;
+
+    // This user matches the layer rule
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+    let layer = client
+        .clone()
+        .get_layer("test_layer".to_string(), user)
+        .await
+        .expect("should succeed");
+    assert_eq!(layer.name, "test_layer".to_string());
+    assert_eq!(layer.rule_id, "layer_rule_1239".to_string());
+    assert_eq!(layer.get::<String>("button_color"), Some("red".to_string()));
+    assert_eq!(layer.get_or("max_items", 0), 20);
+
+    // This user gets the default values
+    let user = StatsigUser::new("1240".to_string(), "production".to_string());
+    let layer = client
+        .get_layer("test_layer".to_string(), user)
+        .await
+        .expect("should succeed");
+    assert_eq!(layer.rule_id, "default".to_string());
+    assert_eq!(
+        layer.get::<String>("button_color"),
+        Some("blue".to_string())
+    );
+    assert_eq!(layer.get::<i64>("missing"), None);
+
+    Ok(())
+}
+
+/// A layer whose rule checks `layer_gate` and delegates to an experiment that checks
+/// `experiment_gate` and owns `button_color`.
+fn expect_delegating_layer_specs(server: &Server) {
+    let public_gate = |name: &str| {
+        json!({
+            "name": name,
+            "type": "feature_gate",
+            "salt": "gate_salt",
+            "enabled": true,
+            "defaultValue": false,
+            "idType": "userID",
+            "rules": [{
+                "name": "public",
+                "id": format!("{}_rule", name),
+                "salt": "public_salt",
+                "passPercentage": 100,
+                "idType": "userID",
+                "returnValue": true,
+                "conditions": [{"type": "public", "idType": "userID"}],
+            }],
+        })
+    };
+    let pass_gate =
+        |gate: &str| json!([{"type": "pass_gate", "targetValue": gate, "idType": "userID"}]);
+    server.expect(
+        Expectation::matching(request::method_path(
+            "GET",
+            "/download_config_specs/api_key.json",
+        ))
+        .times(..)
+        .respond_with(json_encoded(json!({
+            "feature_gates": [public_gate("layer_gate"), public_gate("experiment_gate")],
+            "dynamic_configs": [{
+                "name": "layer_experiment",
+                "type": "dynamic_config",
+                "salt": "experiment_salt",
+                "enabled": true,
+                "defaultValue": {},
+                "idType": "userID",
+                "entity": "experiment",
+                "explicitParameters": ["button_color"],
+                "rules": [{
+                    "name": "treatment",
+                    "id": "treatment_rule",
+                    "salt": "treatment_salt",
+                    "passPercentage": 100,
+                    "idType": "userID",
+                    "returnValue": {"button_color": "green", "max_items": 30},
+                    "conditions": pass_gate("experiment_gate"),
+                }],
+            }],
+            "layer_configs": [{
+                "name": "delegating_layer",
+                "type": "dynamic_config",
+                "salt": "layer_salt",
+                "enabled": true,
+                "defaultValue": {"button_color": "blue", "max_items": 10},
+                "idType": "userID",
+                "entity": "layer",
+                "rules": [{
+                    "name": "allocation",
+                    "id": "allocation_rule",
+                    "salt": "allocation_salt",
+                    "passPercentage": 100,
+                    "idType": "userID",
+                    "returnValue": {},
+                    "configDelegate": "layer_experiment",
+                    "conditions": pass_gate("layer_gate"),
+                }],
+            }],
+            "has_updates": true,
+            "time": 0,
+        }))),
+    );
+}
+
+#[tokio::test]
+async fn test_layer_logs_parameter_exposures() -> Result<()> {
+    let server = Server::run();
+    expect_delegating_layer_specs(&server);
+    expect_id_lists(&server);
+    let events = record_log_emission(&server);
+    let client = connect_client(&server, |_| {}).await;
+
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+    let layer = client
+        .clone()
+        .get_layer("delegating_layer".to_string(), user)
+        .await?;
+    assert_eq!(
+        layer.allocated_experiment_name,
+        Some("layer_experiment".to_string())
+    );
+    client.flush().await;
+    assert!(events.lock().unwrap().is_empty());
+
+    assert_eq!(
+        layer.get::<String>("button_color"),
+        Some("green".to_string())
+    );
+    assert_eq!(layer.get_or("max_items", 0), 30);
+    assert_eq!(layer.get::<i64>("missing"), None);
+    client.flush().await;
+
+    let events = events.lock().unwrap().clone();
+    assert_eq!(events.len(), 2);
+    let exposure = |gates: &[&str]| -> Vec<HashMap<String, String>> {
+        gates
+            .iter()
+            .map(|gate| {
+                HashMap::from([
+                    ("gate".to_string(), gate.to_string()),
+                    ("gateValue".to_string(), "true".to_string()),
+                    ("ruleID".to_string(), format!("{}_rule", gate)),
+                ])
+            })
+            .collect()
+    };
+
+    let explicit = &events[0];
+    assert_eq!(explicit.event.event_name, "statsig::layer_exposure");
+    assert_eq!(explicit.event.metadata["config"], "delegating_layer");
+    assert_eq!(explicit.event.metadata["ruleID"], "treatment_rule");
+    assert_eq!(explicit.event.metadata["parameterName"], "button_color");
+    assert_eq!(explicit.event.metadata["isExplicitParameter"], "true");
+    assert_eq!(
+        explicit.event.metadata["allocatedExperiment"],
+        "layer_experiment"
+    );
+    assert_eq!(
+        explicit.secondary_exposures,
+        exposure(&["layer_gate", "experiment_gate"])
+    );
+
+    let implicit = &events[1];
+    assert_eq!(implicit.event.metadata["parameterName"], "max_items");
+    assert_eq!(implicit.event.metadata["isExplicitParameter"], "false");
+    assert_eq!(implicit.event.metadata["allocatedExperiment"], "");
+    assert_eq!(implicit.secondary_exposures, exposure(&["layer_gate"]));
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+
+use statsig_rdp::{
+    models::{LocalResult, OverrideTarget, StatsigUser},
+    StatsigError,
+};
+
+use common::{create_client, create_client_with, ConfigValue};
+
+pub mod common;
+
+#[tokio::test]
+async fn test_local_checks_match_async_checks() -> Result<()> {
+    let client = create_client().await;
+
+    for user_id in ["1239", "1240"] {
+        let user = StatsigUser::new(user_id.to_string(), "production".to_string());
+
+        let pass = client
+            .clone()
+            .check_gate("test_gate".to_string(), user.clone())
+            .await?;
+        assert_eq!(
+            client.check_gate_local("test_gate", &user)?,
+            LocalResult::Value(pass)
+        );
+
+        let config = client
+            .clone()
+            .get_config::<ConfigValue>("test_dynamic_config".to_string(), user.clone())
+            .await?;
+        let local = client
+            .get_config_local::<ConfigValue>("test_dynamic_config", &user)?
+            .value()
+            .expect("should be evaluated locally");
+        assert_eq!(local.rule_id, config.rule_id);
+        assert_eq!(local.value.map(|v| v.value), config.value.map(|v| v.value));
+
+        let layer = client
+            .clone()
+            .get_layer("test_layer".to_string(), user.clone())
+            .await?;
+        let local = client
+            .get_layer_local("test_layer", &user)?
+            .value()
+            .expect("should be evaluated locally");
+        assert_eq!(local.rule_id, layer.rule_id);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_checks_from_other_threads() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    // Outside of the runtime, with enough checks to fill the exposure queue and flush it
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..1000 {
+                let pass = client.check_gate_local("test_gate", &user).unwrap();
+                assert_eq!(pass, LocalResult::Value(true));
+                let experiment = client
+                    .get_experiment_local::<ConfigValue>("test_dynamic_config", &user)
+                    .unwrap();
+                assert!(experiment.value().is_some());
+            }
+        });
+    });
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_checks_need_network_without_cache() -> Result<()> {
+    let client = create_client_with(|options| options.disable_cache = true).await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    assert_eq!(
+        client.check_gate_local("test_gate", &user)?,
+        LocalResult::NeedsNetwork
+    );
+    assert!(client
+        .get_dynamic_config_local::<ConfigValue>("test_dynamic_config", &user)?
+        .value()
+        .is_none());
+    assert!(client
+        .get_layer_local("test_layer", &user)?
+        .value()
+        .is_none());
+
+    // Overrides don't need the API
+    client.override_gate("test_gate".to_string(), false, OverrideTarget::AllUsers);
+    assert_eq!(
+        client.check_gate_local("test_gate", &user)?,
+        LocalResult::Value(false)
+    );
+
+    let user = StatsigUser::new(String::new(), "production".to_string());
+    assert!(matches!(
+        client.check_gate_local("test_gate", &user),
+        Err(StatsigError::InvalidUser(_))
+    ));
+
+    Ok(())
+}
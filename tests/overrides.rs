@@ -0,0 +1,139 @@
+use anyhow::Result;
+use httptest::Server;
+use serde_json::json;
+
+use statsig_rdp::models::{EvaluationReason, OverrideTarget, StatsigConfig, StatsigUser};
+
+use common::{
+    connect_client, create_client, expect_fetch_config_specs, expect_id_lists, record_log_emission,
+    ConfigValue,
+};
+
+pub mod common;
+
+#[tokio::test]
+async fn test_override_gate() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+    let other_user = StatsigUser::new("1240".to_string(), "production".to_string());
+
+    client.override_gate(
+        "test_gate".to_string(),
+        false,
+        OverrideTarget::UserId("1239".to_string()),
+    );
+    assert!(
+        !client
+            .clone()
+            .check_gate("test_gate".to_string(), user.clone())
+            .await?
+    );
+    assert!(
+        client
+            .clone()
+            .check_gate("test_gate".to_string(), other_user)
+            .await?
+    );
+
+    client.remove_gate_override("test_gate", &OverrideTarget::UserId("1239".to_string()));
+    assert!(client.check_gate("test_gate".to_string(), user).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_override_config_and_experiment() -> Result<()> {
+    let client = create_client().await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    client.override_config(
+        "test_dynamic_config".to_string(),
+        json!({"value": "OVERRIDE"}),
+        OverrideTarget::AllUsers,
+    );
+    let config: StatsigConfig<ConfigValue> = client
+        .clone()
+        .get_config("test_dynamic_config".to_string(), user.clone())
+        .await?;
+    assert_eq!(config.value.map(|v| v.value), Some("OVERRIDE".to_string()));
+    assert_eq!(config.rule_id, "override".to_string());
+    assert_eq!(
+        config.evaluation_details.map(|d| d.reason),
+        Some(EvaluationReason::LocalOverride)
+    );
+
+    client.override_experiment(
+        "unknown_experiment".to_string(),
+        json!({"value": "EXPERIMENT"}),
+        OverrideTarget::AllUsers,
+    );
+    let experiment = client
+        .clone()
+        .get_experiment::<ConfigValue>("unknown_experiment".to_string(), user.clone())
+        .await?;
+    assert_eq!(
+        experiment.value.map(|v| v.value),
+        Some("EXPERIMENT".to_string())
+    );
+    assert_eq!(
+        experiment.evaluation_details.map(|d| d.reason),
+        Some(EvaluationReason::LocalOverride)
+    );
+
+    client.remove_all_overrides();
+    let config: StatsigConfig<ConfigValue> = client
+        .get_config("test_dynamic_config".to_string(), user)
+        .await?;
+    assert_eq!(config.value.map(|v| v.value), Some("DEFAULT".to_string()));
+    assert_eq!(
+        config.evaluation_details.map(|d| d.reason),
+        Some(EvaluationReason::LocalEvaluation)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_overrides_log_no_exposures() -> Result<()> {
+    let server = Server::run();
+    expect_fetch_config_specs(&server);
+    expect_id_lists(&server);
+    let events = record_log_emission(&server);
+    let client = connect_client(&server, |_| {}).await;
+    let user = StatsigUser::new("1239".to_string(), "production".to_string());
+
+    client.override_gate("test_gate".to_string(), false, OverrideTarget::AllUsers);
+    client.override_config(
+        "test_dynamic_config".to_string(),
+        json!({"value": "OVERRIDE"}),
+        OverrideTarget::AllUsers,
+    );
+    assert!(
+        !client
+            .clone()
+            .check_gate("test_gate".to_string(), user.clone())
+            .await?
+    );
+    let config: StatsigConfig<ConfigValue> = client
+        .clone()
+        .get_config("test_dynamic_config".to_string(), user.clone())
+        .await?;
+    assert_eq!(config.rule_id, "override".to_string());
+    client.flush().await;
+    assert!(events.lock().unwrap().is_empty());
+
+    // Checks that aren't overridden are still logged
+    client.remove_all_overrides();
+    assert!(
+        client
+            .clone()
+            .check_gate("test_gate".to_string(), user)
+            .await?
+    );
+    client.flush().await;
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event.event_name, "statsig::gate_exposure");
+
+    Ok(())
+}